@@ -3,22 +3,53 @@ program: declaration* EOF;
 declaration:
 	varDecl			# variable
 	| statement		# statment
-	| functionDecl	# function;
+	| functionDecl	# function
+	| enumDecl		# enumeration;
 
-functionDecl: 'fun' id = IDENTIFIER '()' body = block;
+functionDecl:
+	'fun' id = IDENTIFIER '(' parameters? ')' (
+		':' returnType = typeName
+	)? body = block;
+parameters:
+	params += parameter (',' params += parameter)* (
+		',' rest = restParameter
+	)?
+	| rest = restParameter;
+parameter:
+	IDENTIFIER (':' paramType = typeName)? ('=' value = expression)?;
+restParameter: ELLIPSIS IDENTIFIER;
 
-varDecl: 'var' IDENTIFIER ( '=' expr = expression)? ';';
+enumDecl:
+	'enum' IDENTIFIER LCURL enumVariant (',' enumVariant)* ','? RCURL;
+enumVariant:
+	IDENTIFIER ('(' IDENTIFIER (',' IDENTIFIER)* ')')?;
+
+varDecl:
+	'var' IDENTIFIER (':' varType = typeName)? ('=' expr = expression)? ';'
+	| 'var' target = listPattern '=' expr = expression ';';
+listPattern:
+	LBRACK (
+		IDENTIFIER (',' IDENTIFIER)* (',' restParameter)?
+		| restParameter
+	)? RBRACK;
+typeName: IDENTIFIER | 'nil';
 statement:
 	exprStmt
 	| ifStmt
 	| printStmt
 	| whileStmt
 	| forStmt
+	| forInStmt
 	| breakStmt
+	| continueStmt
+	| matchStmt
+	| yieldStmt
+	| returnStmt
 	| block;
 
 block: LCURL declaration* RCURL;
 breakStmt: 'break' ';';
+continueStmt: 'continue' ';';
 whileStmt:
 	'while' '(' condition = logic_or ')' body = statement;
 
@@ -26,6 +57,21 @@ forStmt:
 	'for' '(' (initializer = exprStmt | forvar = varDecl | ';') (
 		condition = logic_or
 	)? ';' (increment = expression)? ')' body = statement;
+forInStmt:
+	'for' '(' IDENTIFIER 'in' iterable = expression ')' body = statement;
+matchStmt:
+	'match' '(' subject = expression ')' LCURL matchArm* RCURL;
+matchArm: pattern ( 'if' guard = expression)? '=>' body = statement;
+pattern: alternatives += patternAtom ( '|' alternatives += patternAtom)*;
+patternAtom:
+	low = literal ( ( DOTDOT | DOTDOTEQ) high = literal)?
+	| IDENTIFIER
+	| variantPattern
+	| listPattern;
+variantPattern: IDENTIFIER '(' (IDENTIFIER (',' IDENTIFIER)*)? ')';
+literal: 'true' | 'false' | 'nil' | MINUS? NUMBER | STRING;
+yieldStmt: 'yield' value = expression ';';
+returnStmt: 'return' value = expression? ';';
 exprStmt: expression ';';
 printStmt: 'print' exp = expression ';';
 ifStmt:
@@ -35,13 +81,16 @@ ifStmt:
 expression: assignment;
 
 assignment:
-	IDENTIFIER '=' iter = assignment	# assignment_alt
+	IDENTIFIER '=' iter = assignment				# assignment_alt
+	| target = listPattern '=' value = assignment	# destructure_alt
 	| logic_or							# logic_or_alt;
 
 logic_or: left = logic_and ( 'or' right = logic_and)?;
 logic_and: left = equality ( 'and' right = equality)?;
 equality: left = comparison ( ( NEQ | EQ) right = comparison)?;
-comparison: left = term ( ( GT | GTE | LT | LTE) right = term)?;
+comparison:
+	left = rangeExpr ( ( GT | GTE | LT | LTE) right = rangeExpr)?;
+rangeExpr: left = term ( ( DOTDOT | DOTDOTEQ) right = term)?;
 
 term: left = factor ( ( MINUS | PLUS) right = factor)*;
 factor: left = unary ( ( SLASH | STAR) right = unary)*;
@@ -51,7 +100,8 @@ unary: (BANG | MINUS) right = unary	# unary_alt
 	| callfun						# callfun_alt;
 
 callfun: id = primary '(' arguments? ')';
-arguments: expression ( ',' expression)*;
+arguments: argument ( ',' argument)*;
+argument: ( IDENTIFIER ':')? value = expression;
 primary:
 	'true'					# bool_true
 	| 'false'				# bool_false
@@ -59,10 +109,13 @@ primary:
 	| NUMBER				# number
 	| STRING				# strval
 	| IDENTIFIER			# identifier
-	| '(' expression ')'	# group;
+	| '(' expression ')'	# group
+	| LBRACK (expression (',' expression)*)? RBRACK	# list;
 
 LCURL: '{';
 RCURL: '}';
+LBRACK: '[';
+RBRACK: ']';
 BANG: '!';
 MINUS: '-';
 SLASH: '/';
@@ -74,12 +127,24 @@ LT: '<';
 LTE: '<=';
 EQ: '==';
 NEQ: '!=';
+DOTDOT: '..';
+DOTDOTEQ: '..=';
+ELLIPSIS: '...';
 WS: [ \t\r\n]+ -> skip; //channel(HIDDEN);
-NUMBER: DIGIT+ ( '.' DIGIT+)?;
+// hex 0xFF, binary 0b1010, octal 0o17, decimal with exponent and _ separators
+// trailing letters are kept in the token so malformed literals can be reported whole
+NUMBER:
+	DIGIT NUM_CHAR* ('.' DIGIT NUM_CHAR*)?
+	| DIGIT (DIGIT | '_')* ('.' DIGIT (DIGIT | '_')*)? [eE] [+-] DIGIT NUM_CHAR*;
+fragment NUM_CHAR: [0-9a-zA-Z_];
 STRING: '"' .*? '"';
-IDENTIFIER: ALPHA ( ALPHA | DIGIT)*;
-ALPHA: 'a' .. 'z' | 'A' .. 'Z' | '_';
-DIGIT: '0' .. '9';
-LINE_COMMENT:
-	'//' .*? '\r'? '\n' -> skip; // Match "//" stuff '\n'
-COMMENT: '/*' .*? '*/' -> skip; // Match "/*" stuff "*/"
\ No newline at end of file
+IDENTIFIER: ID_START ID_CONTINUE*;
+fragment ID_START: [\p{XID_Start}] | '_';
+fragment ID_CONTINUE: [\p{XID_Continue}];
+fragment DIGIT: '0' .. '9';
+// "/// text" documents the declaration that follows, kept for DocVisit
+DOC_COMMENT: '///' (~[/\r\n] ~[\r\n]*)? -> channel(HIDDEN);
+LINE_COMMENT: '//' ~[\r\n]* -> skip; // also ends at the end of the file
+COMMENT: '/*' (COMMENT | .)*? '*/' -> skip; // block comments nest
+// anything else, reported as an invalid character by ErrDetectVisit
+INVALID: .;
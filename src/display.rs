@@ -0,0 +1,71 @@
+use std::rc::Rc;
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+
+/*
+
+    The one place that turns a value into the text a script sees, used by print
+    and by the str() builtin.
+    Strings show without quotes at the top level and quoted inside a list or an
+    enum value, so that [1, "1"] can be told apart from [1, 1].
+    Lists that are already being shown further up are printed as [...] so that a
+    list holding itself cannot recurse forever.
+
+*/
+impl<'a> InterpVisit<'a> {
+    pub fn display(&self, val: &TermValue) -> String {
+        self.display_in(val, false, &mut Vec::new())
+    }
+
+    // open holds the lists between the top level value and this one
+    fn display_in(
+        &self,
+        val: &TermValue,
+        nested: bool,
+        open: &mut Vec<*const Vec<TermValue>>,
+    ) -> String {
+        match val {
+            TermValue::Number(x) => format!("{}", x),
+            TermValue::True => "true".to_string(),
+            TermValue::False => "false".to_string(),
+            // statements and calls that produce nothing show as nil
+            TermValue::Nil | TermValue::Empty => "nil".to_string(),
+            TermValue::StringValue(s) if nested => format!("{:?}", s),
+            TermValue::StringValue(s) => s.clone(),
+            TermValue::Error(msg) => format!("<error {}>", msg),
+            TermValue::Function(fidx) => format!("<fn {}>", self.function_name(*fidx)),
+            TermValue::Native(_) => "<native fn>".to_string(),
+            TermValue::Constructor(def, variant) => format!("<fn {}>", def.variants[*variant].0),
+            TermValue::Range(range) if range.step != 1.0 => {
+                format!("range({}, {}, {})", range.start, range.end, range.step)
+            }
+            TermValue::Range(range) => {
+                let op = if range.inclusive { "..=" } else { ".." };
+                format!("{}{}{}", range.start, op, range.end)
+            }
+            TermValue::List(items) => {
+                let ptr = Rc::as_ptr(items);
+                if open.contains(&ptr) {
+                    return "[...]".to_string();
+                }
+                open.push(ptr);
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| self.display_in(item, true, open))
+                    .collect();
+                open.pop();
+                format!("[{}]", items.join(", "))
+            }
+            TermValue::Generator(_) => "<generator>".to_string(),
+            TermValue::Enum(e) if e.fields.is_empty() => e.name().to_string(),
+            TermValue::Enum(e) => {
+                let fields: Vec<String> = e
+                    .fields
+                    .iter()
+                    .map(|field| self.display_in(field, true, open))
+                    .collect();
+                format!("{}({})", e.name(), fields.join(", "))
+            }
+        }
+    }
+}
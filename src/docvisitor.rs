@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::antlr::loxparser::{
+    FunctionDeclContext, FunctionDeclContextAttrs, LoxParserContextType, VarDeclContext,
+    VarDeclContextAttrs, DOC_COMMENT,
+};
+use crate::antlr::loxvisitor::LoxVisitorCompat;
+use antlr_rust::int_stream::IntStream;
+use antlr_rust::parser_rule_context::ParserRuleContext;
+use antlr_rust::token::Token;
+use antlr_rust::token_factory::CommonTokenFactory;
+use antlr_rust::token_stream::TokenStream;
+use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat};
+
+/*
+
+    DocVisit attaches /// doc comments to the function and variable declarations
+    they precede, for tooling that wants to show them.
+    The comments are on the hidden channel so the parser never sees them, they are
+    read back from the token stream by doc_comments() and keyed by the index of
+    the first token after them, which is the 'fun' or 'var' of the declaration.
+
+*/
+pub struct Doc {
+    pub name: String,
+    pub line: isize,
+    pub text: String,
+}
+
+pub struct DocVisit {
+    pub docs: Vec<Doc>,
+    comments: HashMap<isize, String>,
+    val: (),
+}
+
+// the text of each run of consecutive doc comment lines, by the index of the
+// token that follows the run
+pub fn doc_comments(
+    tokens: &dyn TokenStream<'_, TF = CommonTokenFactory>,
+) -> HashMap<isize, String> {
+    let mut comments = HashMap::new();
+    let mut lines: Vec<String> = Vec::new();
+    for i in 0..tokens.size() {
+        let token = tokens.get(i);
+        if token.get_token_type() == DOC_COMMENT {
+            let text = token.get_text().strip_prefix("///").unwrap_or_default();
+            lines.push(text.strip_prefix(' ').unwrap_or(text).to_string());
+        } else if !lines.is_empty() {
+            comments.insert(i, lines.join("\n"));
+            lines.clear();
+        }
+    }
+    comments
+}
+
+impl DocVisit {
+    pub fn new(comments: HashMap<isize, String>) -> Self {
+        Self {
+            docs: Vec::new(),
+            comments,
+            val: (),
+        }
+    }
+    fn attach(&mut self, name: String, start: isize, line: isize) {
+        if let Some(text) = self.comments.get(&start) {
+            self.docs.push(Doc {
+                name,
+                line,
+                text: text.clone(),
+            });
+        }
+    }
+}
+
+impl ParseTreeVisitorCompat<'_> for DocVisit {
+    type Node = LoxParserContextType;
+    type Return = ();
+    fn temp_result(&mut self) -> &mut Self::Return {
+        &mut self.val
+    }
+}
+
+impl<'a> LoxVisitorCompat<'a> for DocVisit {
+    fn visit_functionDecl(&mut self, ctx: &FunctionDeclContext<'a>) -> Self::Return {
+        let name = ctx.IDENTIFIER().unwrap().get_text();
+        self.attach(name, ctx.start().get_token_index(), ctx.start().get_line());
+        self.visit_children(ctx)
+    }
+    fn visit_varDecl(&mut self, ctx: &VarDeclContext<'a>) -> Self::Return {
+        // a destructuring declaration is listed under its whole pattern
+        let name = match ctx.IDENTIFIER() {
+            Some(id) => id.get_text(),
+            None => ctx.target.as_ref().unwrap().get_text(),
+        };
+        self.attach(name, ctx.start().get_token_index(), ctx.start().get_line());
+    }
+}
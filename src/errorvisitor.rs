@@ -1,27 +1,30 @@
-use crate::antlr::{loxparser::LoxParserContextType, loxvisitor::LoxVisitorCompat};
-use antlr_rust::atn_config_set::ATNConfigSet;
-use antlr_rust::dfa::DFA;
+use crate::antlr::{
+    loxparser::{LoxParserContextType, INVALID},
+    loxvisitor::LoxVisitorCompat,
+};
 use antlr_rust::recognizer::Recognizer;
+use antlr_rust::token::Token;
 use antlr_rust::{
     error_listener::ErrorListener,
-    tree::{ErrorNode, ParseTree, ParseTreeVisitorCompat},
+    tree::{ErrorNode, ParseTreeVisitorCompat},
 };
-use bit_set::BitSet;
 
 /*
 
     Errdetectvisitor is used to detect that a parsing error has occurred.
     It seems impossible to get the error from the parser or from the ErrorHandler
     But the parser does insert ErrorNodes into the tree, so we can detect those.
-    So this visitor makes a pass over the tree and if it finds an ErrorNode it returns it
+    So this visitor makes a pass over the tree and if it finds an ErrorNode it flags it
     Run before the vistor that does the actual work, so that the error is detected before
+    The error itself has already been reported by MyErrorListener, so only the
+    failure is flagged here, the message is not repeated
 
 */
 #[derive(Default, Debug, PartialEq)]
 pub enum ErrVal {
     #[default]
     Empty,
-    Error(String),
+    Error,
 }
 pub struct ErrDetectVisit {
     pub val: ErrVal,
@@ -47,8 +50,8 @@ impl ParseTreeVisitorCompat<'_> for ErrDetectVisit {
             aggregate
         }
     }
-    fn visit_error_node(&mut self, node: &ErrorNode<'_, Self::Node>) -> Self::Return {
-        ErrVal::Error(node.get_text())
+    fn visit_error_node(&mut self, _node: &ErrorNode<'_, Self::Node>) -> Self::Return {
+        ErrVal::Error
     }
 }
 impl LoxVisitorCompat<'_> for ErrDetectVisit {
@@ -64,53 +67,31 @@ impl MyErrorListener {
     }
 }
 
+// only syntax errors are reported, ambiguity reports are parser diagnostics
+// that the default methods ignore
 impl<'a, T: Recognizer<'a>> ErrorListener<'a, T> for MyErrorListener {
     fn syntax_error(
         &self,
         _recognizer: &T,
-        _offending_symbol: Option<&<T::TF as antlr_rust::token_factory::TokenFactory<'a>>::Inner>,
+        offending_symbol: Option<&<T::TF as antlr_rust::token_factory::TokenFactory<'a>>::Inner>,
         line: isize,
         column: isize,
         msg: &str,
         _error: Option<&antlr_rust::errors::ANTLRError>,
     ) {
-        println!("a line {}:{} {}", line, column, msg);
-    }
-
-    fn report_ambiguity(
-        &self,
-        _recognizer: &T,
-        _dfa: &DFA,
-        start_index: isize,
-        stop_index: isize,
-        _exact: bool,
-        _ambig_alts: &BitSet,
-        _configs: &ATNConfigSet,
-    ) {
-        println!("b error {} {}", start_index, stop_index   );
-    }
-
-    fn report_attempting_full_context(
-        &self,
-        _recognizer: &T,
-        _dfa: &DFA,
-        start_index: isize,
-        stop_index: isize,
-        _conflicting_alts: &BitSet,
-        _configs: &ATNConfigSet,
-    ) {
-        println!("c error {} {}", start_index, stop_index   );
-    }
-
-    fn report_context_sensitivity(
-        &self,
-        _recognizer: &T,
-        _dfa: &DFA,
-        start_index: isize,
-        stop_index: isize,
-        _prediction: isize,
-        _configs: &ATNConfigSet,
-    ) {
-        println!("d error {} {}", start_index, stop_index   );
+        // characters the lexer could not match are INVALID tokens, say so
+        // instead of what the parser expected in their place
+        if let Some(token) = offending_symbol {
+            if token.get_token_type() == INVALID {
+                eprintln!(
+                    "Error: line {}:{} invalid character {:?}",
+                    line,
+                    column,
+                    token.get_text()
+                );
+                return;
+            }
+        }
+        eprintln!("Error: line {}:{} {}", line, column, msg);
     }
 }
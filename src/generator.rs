@@ -0,0 +1,386 @@
+use std::rc::{Rc, Weak};
+
+use crate::antlr::loxparser::{
+    BlockContextAll, BlockContextAttrs, DeclarationContextAll, ForInStmtContextAll,
+    ForInStmtContextAttrs, ForStmtContextAll, LoxParserContext, RULE_functionDecl, RULE_yieldStmt,
+    StatementContextAll, StatementContextAttrs, StatmentContextAttrs, WhileStmtContextAll,
+};
+use crate::interpvisitor::{condition_error, ExecutionState, InterpVisit, TermValue};
+use crate::iterator::{self, LoxIterator};
+use antlr_rust::rule_context::RuleContext;
+use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat, Tree};
+
+/*
+
+    Generators cannot be run by the recursive InterpVisit, a suspended generator
+    would need to keep part of the rust call stack alive.
+    Instead the statements of a generator body that contain a yield are run here
+    one step at a time, with the work still to do kept in an explicit list of steps.
+    Statements without a yield are handed to InterpVisit in one go, so only blocks,
+    if, match and the loops need to be understood here.
+    While suspended a generator holds on to its scopes, from the globals down to
+    the innermost block, and its loop depth. They are put in place of the
+    interpreter's own while it resumes.
+    The generators themselves live in InterpVisit::generators, a TermValue only
+    holds a GeneratorRef naming the slot. Once no value refers to a slot any more
+    the generator can never run again, its scopes are dropped and the slot is
+    reused by the next generator. A finished generator drops its scopes at once.
+
+*/
+enum Step<'a> {
+    Stmt(Rc<StatementContextAll<'a>>),
+    // scoped is false for the function body, which shares the parameters' scope
+    Block {
+        ctx: Rc<BlockContextAll<'a>>,
+        next: usize,
+        scoped: bool,
+    },
+    While(Rc<WhileStmtContextAll<'a>>),
+    // the increment is skipped until the body has run once
+    For {
+        ctx: Rc<ForStmtContextAll<'a>>,
+        first: bool,
+    },
+    ForIn {
+        ctx: Rc<ForInStmtContextAll<'a>>,
+        iter: LoxIterator,
+    },
+    // leaves the scope of a match arm or of one for-in iteration
+    PopScope,
+}
+
+// what a TermValue::Generator holds, the index of the generator's slot
+#[derive(Debug)]
+pub struct GeneratorRef(usize);
+
+// a slot of InterpVisit::generators
+pub struct GeneratorSlot<'a> {
+    handle: Weak<GeneratorRef>,
+    generator: Generator<'a>,
+}
+
+#[derive(Default)]
+pub struct Generator<'a> {
+    steps: Vec<Step<'a>>,
+    frames: Vec<ExecutionState>,
+    loop_depth: u32,
+    done: bool,
+}
+
+impl<'a> Generator<'a> {
+    // frames are the scopes of the call, ending with the one holding the parameters
+    pub fn new(frames: Vec<ExecutionState>, body: Rc<BlockContextAll<'a>>) -> Self {
+        Self {
+            steps: vec![Step::Block {
+                ctx: body,
+                next: 0,
+                scoped: false,
+            }],
+            frames,
+            loop_depth: 0,
+            done: false,
+        }
+    }
+    fn finished() -> Self {
+        Self {
+            done: true,
+            ..Self::default()
+        }
+    }
+}
+
+// true if there is a yield below node, not counting nested function declarations
+pub fn contains_yield<'a>(node: &(dyn LoxParserContext<'a> + 'a)) -> bool {
+    node.get_children().any(|child| {
+        let rule = child.get_rule_index();
+        rule == RULE_yieldStmt || (rule != RULE_functionDecl && contains_yield(&*child))
+    })
+}
+
+// a value that has to be handed back to the caller of resume_generator
+fn failed(val: TermValue) -> Option<TermValue> {
+    if let TermValue::Error(_) = val {
+        return Some(val);
+    }
+    None
+}
+
+impl<'a> InterpVisit<'a> {
+    // a suspended generator for the body of a call, frames are the scopes of the
+    // call ending with the one holding the parameters
+    pub(crate) fn new_generator(
+        &mut self,
+        frames: Vec<ExecutionState>,
+        body: Rc<BlockContextAll<'a>>,
+    ) -> TermValue {
+        // nothing can resume a generator no value refers to any more, drop its
+        // scopes and reuse the first such slot
+        let mut free = None;
+        for (i, slot) in self.generators.iter_mut().enumerate() {
+            if slot.handle.strong_count() == 0 {
+                slot.generator = Generator::finished();
+                free.get_or_insert(i);
+            }
+        }
+        let gidx = free.unwrap_or(self.generators.len());
+        let handle = Rc::new(GeneratorRef(gidx));
+        let slot = GeneratorSlot {
+            handle: Rc::downgrade(&handle),
+            generator: Generator::new(frames, body),
+        };
+        if gidx == self.generators.len() {
+            self.generators.push(slot);
+        } else {
+            self.generators[gidx] = slot;
+        }
+        TermValue::Generator(handle)
+    }
+
+    // runs a generator up to its next yield and returns the yielded value,
+    // None once the body has finished
+    pub fn resume_generator(&mut self, handle: &GeneratorRef) -> Option<TermValue> {
+        let gidx = handle.0;
+        // taken out while it runs, a generator that resumes itself finds it finished
+        let mut gen =
+            std::mem::replace(&mut self.generators[gidx].generator, Generator::finished());
+        if gen.done {
+            self.generators[gidx].generator = gen;
+            return None;
+        }
+        let caller = std::mem::replace(&mut self.state, std::mem::take(&mut gen.frames));
+        let caller_loops = std::mem::replace(&mut self.loop_depth, gen.loop_depth);
+
+        let result = self.run_steps(&mut gen);
+
+        gen.loop_depth = std::mem::replace(&mut self.loop_depth, caller_loops);
+        gen.frames = std::mem::replace(&mut self.state, caller);
+        if matches!(result, None | Some(TermValue::Error(_))) {
+            gen = Generator::finished();
+        }
+        self.generators[gidx].generator = gen;
+        result
+    }
+
+    fn run_steps(&mut self, gen: &mut Generator<'a>) -> Option<TermValue> {
+        loop {
+            // a return statement ends the generator, the resolver makes sure it has no value
+            if self.returned.take().is_some() {
+                return None;
+            }
+            if self.break_requested || self.continue_requested {
+                self.unwind(gen);
+            }
+            let result = match gen.steps.pop()? {
+                Step::Stmt(stmt) => self.step_statement(gen, stmt),
+                Step::Block { ctx, next, scoped } => {
+                    let decls = ctx.declaration_all();
+                    if next == decls.len() {
+                        if scoped {
+                            self.state.pop();
+                        }
+                        continue;
+                    }
+                    gen.steps.push(Step::Block {
+                        ctx: ctx.clone(),
+                        next: next + 1,
+                        scoped,
+                    });
+                    match decls[next].as_ref() {
+                        DeclarationContextAll::StatmentContext(c) => {
+                            gen.steps.push(Step::Stmt(c.statement().unwrap()));
+                            None
+                        }
+                        decl => failed(self.visit(decl)),
+                    }
+                }
+                Step::While(ctx) => {
+                    let cond = self.visit(&*ctx.condition.as_ref().unwrap().as_ref());
+                    match cond {
+                        TermValue::Error(_) => Some(cond),
+                        TermValue::True => {
+                            let body = ctx.body.clone().unwrap();
+                            gen.steps.push(Step::While(ctx));
+                            gen.steps.push(Step::Stmt(body));
+                            None
+                        }
+                        TermValue::False => {
+                            self.loop_depth -= 1;
+                            None
+                        }
+                        _ => Some(condition_error(&**ctx.condition.as_ref().unwrap(), &cond)),
+                    }
+                }
+                Step::For { ctx, first } => self.step_for(gen, ctx, first),
+                Step::ForIn { ctx, mut iter } => match iter.next_item(self) {
+                    Some(TermValue::Error(err)) => Some(TermValue::Error(err)),
+                    Some(item) => {
+                        // every iteration gets a fresh scope holding the loop variable
+                        let scope = ExecutionState::new();
+                        scope
+                            .variables
+                            .borrow_mut()
+                            .insert(ctx.IDENTIFIER().unwrap().get_text(), item);
+                        self.state.push(scope);
+                        let body = ctx.body.clone().unwrap();
+                        gen.steps.push(Step::ForIn { ctx, iter });
+                        gen.steps.push(Step::PopScope);
+                        gen.steps.push(Step::Stmt(body));
+                        None
+                    }
+                    None => {
+                        self.loop_depth -= 1;
+                        None
+                    }
+                },
+                Step::PopScope => {
+                    self.state.pop();
+                    None
+                }
+            };
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+
+    // returns Some with the value of a yield or an Error, None to carry on
+    fn step_statement(
+        &mut self,
+        gen: &mut Generator<'a>,
+        stmt: Rc<StatementContextAll<'a>>,
+    ) -> Option<TermValue> {
+        if !contains_yield(&*stmt) {
+            return failed(self.visit(&*stmt));
+        }
+        if let Some(yield_stmt) = stmt.yieldStmt() {
+            return Some(self.visit(&*yield_stmt.value.as_ref().unwrap().as_ref()));
+        }
+        if let Some(block) = stmt.block() {
+            self.state.push(ExecutionState::new());
+            gen.steps.push(Step::Block {
+                ctx: block,
+                next: 0,
+                scoped: true,
+            });
+        } else if let Some(if_stmt) = stmt.ifStmt() {
+            let cond = self.visit(&*if_stmt.condition.as_ref().unwrap().as_ref());
+            match cond {
+                TermValue::Error(_) => return Some(cond),
+                TermValue::True => {
+                    gen.steps
+                        .push(Step::Stmt(if_stmt.thenBranch.clone().unwrap()));
+                }
+                _ => {
+                    if let Some(else_branch) = if_stmt.elseBranch.clone() {
+                        gen.steps.push(Step::Stmt(else_branch));
+                    }
+                }
+            }
+        } else if let Some(while_stmt) = stmt.whileStmt() {
+            self.loop_depth += 1;
+            gen.steps.push(Step::While(while_stmt));
+        } else if let Some(for_stmt) = stmt.forStmt() {
+            // the loop variable lives in its own scope around the loop
+            self.state.push(ExecutionState::new());
+            let init = if let Some(forvar) = for_stmt.forvar.as_ref() {
+                self.visit(&*forvar.as_ref())
+            } else if let Some(initializer) = for_stmt.initializer.as_ref() {
+                self.visit(&*initializer.as_ref())
+            } else {
+                TermValue::Empty
+            };
+            if let TermValue::Error(_) = init {
+                return Some(init);
+            }
+            self.loop_depth += 1;
+            gen.steps.push(Step::For {
+                ctx: for_stmt,
+                first: true,
+            });
+        } else if let Some(for_in) = stmt.forInStmt() {
+            let iterable = self.visit(&*for_in.iterable.as_ref().unwrap().as_ref());
+            if let TermValue::Error(_) = iterable {
+                return Some(iterable);
+            }
+            let iter = match iterator::iterate(&iterable) {
+                Ok(iter) => iter,
+                Err(err) => return Some(err),
+            };
+            self.loop_depth += 1;
+            gen.steps.push(Step::ForIn { ctx: for_in, iter });
+        } else if let Some(match_stmt) = stmt.matchStmt() {
+            match self.select_arm(&match_stmt) {
+                Err(err) => return Some(err),
+                Ok(None) => {}
+                Ok(Some((scope, body))) => {
+                    self.state.push(scope);
+                    gen.steps.push(Step::PopScope);
+                    gen.steps.push(Step::Stmt(body));
+                }
+            }
+        }
+        None
+    }
+
+    fn step_for(
+        &mut self,
+        gen: &mut Generator<'a>,
+        ctx: Rc<ForStmtContextAll<'a>>,
+        first: bool,
+    ) -> Option<TermValue> {
+        if !first {
+            if let Some(increment) = ctx.increment.as_ref() {
+                let val = self.visit(&*increment.as_ref());
+                if let TermValue::Error(_) = val {
+                    return Some(val);
+                }
+            }
+        }
+        let cond = match ctx.condition.as_ref() {
+            Some(cond) => self.visit(&*cond.as_ref()),
+            None => TermValue::True,
+        };
+        match cond {
+            TermValue::Error(_) => return Some(cond),
+            TermValue::True => {
+                let body = ctx.body.clone().unwrap();
+                gen.steps.push(Step::For { ctx, first: false });
+                gen.steps.push(Step::Stmt(body));
+            }
+            TermValue::False => {
+                self.loop_depth -= 1;
+                self.state.pop();
+            }
+            _ => return Some(condition_error(&**ctx.condition.as_ref().unwrap(), &cond)),
+        }
+        None
+    }
+
+    // drops the steps up to the innermost loop after a break or continue
+    fn unwind(&mut self, gen: &mut Generator<'a>) {
+        while let Some(step) = gen.steps.pop() {
+            match step {
+                Step::While(_) | Step::For { .. } | Step::ForIn { .. } => {
+                    if self.continue_requested {
+                        self.continue_requested = false;
+                        gen.steps.push(match step {
+                            Step::For { ctx, .. } => Step::For { ctx, first: false },
+                            step => step,
+                        });
+                        return;
+                    }
+                    self.break_requested = false;
+                    self.loop_depth -= 1;
+                    if let Step::For { .. } = step {
+                        self.state.pop();
+                    }
+                    return;
+                }
+                Step::PopScope | Step::Block { scoped: true, .. } => {
+                    self.state.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+}
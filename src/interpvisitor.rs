@@ -2,59 +2,635 @@ use crate::{
     antlr::{
         self,
         loxparser::{
-            Assignment_altContextAttrs, ComparisonContextAttrs, EqualityContextAttrs,
-            FactorContextAttrs, FunctionDeclContextAttrs, GroupContextAttrs, IdentifierContext,
-            LoxParserContextType, PrintStmtContext, TermContextAttrs, Unary_altContextAttrs,
-            VarDeclContext, VarDeclContextAttrs,
+            ArgumentContextAttrs, ArgumentsContextAttrs, Assignment_altContextAttrs,
+            CallfunContextAttrs, ComparisonContextAttrs, EnumDeclContext, EnumDeclContextAttrs,
+            EnumVariantContextAttrs, EqualityContextAttrs, FactorContextAttrs, ForInStmtContext,
+            ForInStmtContextAttrs, FunctionDeclContextAttrs, GroupContextAttrs, IdentifierContext,
+            ListContext, ListContextAttrs, ListPatternContextAll, ListPatternContextAttrs,
+            LiteralContext, LiteralContextAttrs, LoxParserContext, LoxParserContextType,
+            MatchArmContextAttrs, MatchStmtContext, MatchStmtContextAttrs, ParameterContextAttrs,
+            ParametersContextAll, PatternAtomContextAttrs, PatternContextAll, PrintStmtContext,
+            RangeExprContext, RangeExprContextAttrs, RestParameterContextAttrs, ReturnStmtContext,
+            StatementContextAll, TermContextAttrs, Unary_altContextAttrs, VarDeclContext,
+            VarDeclContextAttrs, VariantPatternContextAttrs, YieldStmtContext,
         },
         loxvisitor::LoxVisitorCompat,
     },
+    generator::{self, GeneratorRef, GeneratorSlot},
+    iterator,
+    natives::{self, NativeFunction},
+    number,
+    randlib::Rng,
+    syslib,
+    timelib::{Clock, SystemClock},
     trace,
 };
 
 use antlr::loxparser::{
-    Assignment_altContext, Bool_falseContext, Bool_trueContext, ComparisonContext, EqualityContext,
-    ExpressionContext, GroupContext, Logic_andContext, Logic_orContext, NilContext, NumberContext,
-    StrvalContext, Unary_altContext,
+    Assignment_altContext, Bool_falseContext, Bool_trueContext, ComparisonContext,
+    Destructure_altContext, EqualityContext, ExpressionContext, GroupContext, Logic_andContext,
+    Logic_orContext, NilContext, NumberContext, StrvalContext, Unary_altContext,
 };
+use antlr_rust::parser_rule_context::ParserRuleContext;
+use antlr_rust::token::Token;
 use antlr_rust::tree::{ErrorNode, ParseTree, ParseTreeVisitorCompat, Tree};
 
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::unreachable;
 use std::{collections::HashMap, rc::Rc};
-struct ExecutionState<'a> {
-    pub variables: HashMap<String, TermValue>,
+
+// the variables of one scope, shared with the functions declared in it
+pub(crate) type Scope = Rc<RefCell<HashMap<String, TermValue>>>;
+
+pub(crate) struct ExecutionState {
+    pub variables: Scope,
     pub return_value: TermValue,
-    pub functions: Vec<Rc<antlr::loxparser::BlockContextAll<'a>>>,
 }
 
-impl<'a> ExecutionState<'a> {
+impl ExecutionState {
     pub fn new() -> Self {
+        Self::with_scope(Scope::default())
+    }
+    pub fn with_scope(variables: Scope) -> Self {
         Self {
-            variables: HashMap::new(),
-            functions: Vec::new(),
+            variables,
             return_value: TermValue::Empty,
         }
     }
 }
+// a declared function, TermValue::Function holds its index in InterpVisit::functions
+struct FunctionDef<'a> {
+    name: String,
+    params: Option<Rc<ParametersContextAll<'a>>>,
+    body: Rc<antlr::loxparser::BlockContextAll<'a>>,
+    // the body contains a yield, calling it creates a generator
+    is_generator: bool,
+    // the scopes around the declaration, a call runs on top of these
+    // rather than on top of the caller's scopes
+    closure: Vec<Scope>,
+}
 pub struct InterpVisit<'a> {
     // val: TermValue,
-    state: Vec<ExecutionState<'a>>,
-    break_requested: bool,
-    loop_depth: u32,
+    pub(crate) state: Vec<ExecutionState>,
+    // the index in state of the scope the resolver bound each variable
+    // reference to, by the index of the reference's token
+    pub locals: HashMap<isize, usize>,
+    functions: Vec<FunctionDef<'a>>,
+    // suspended generators, TermValue::Generator names the slot
+    pub(crate) generators: Vec<GeneratorSlot<'a>>,
+    pub(crate) break_requested: bool,
+    pub(crate) continue_requested: bool,
+    // set by a return statement until the call it leaves picks up the value
+    pub(crate) returned: Option<TermValue>,
+    pub(crate) loop_depth: u32,
+    pub warn_non_exhaustive: bool,
+    // match statements (line, column) already warned about
+    warned: HashSet<(isize, isize)>,
+    // where clock(), time() and sleep() get the time from
+    pub clock: Box<dyn Clock>,
+    // the generator behind random() and the other random builtins
+    pub rng: Rng,
+    // set by exit(), the error it returns only unwinds the script
+    pub exit_code: Option<i32>,
 }
 
 impl<'a> InterpVisit<'a> {
     pub fn new() -> Self {
+        let mut globals = HashMap::new();
+        for native in natives::globals() {
+            globals.insert(native.name.to_string(), TermValue::Native(*native));
+        }
+        for (name, value) in natives::constants() {
+            globals.insert(name.to_string(), TermValue::Number(*value));
+        }
+        globals.insert(
+            syslib::ARGS.to_string(),
+            TermValue::List(Rc::new(Vec::new())),
+        );
         Self {
             //val: TermValue::Empty,
-            state: vec![ExecutionState::new()],
+            state: vec![ExecutionState::with_scope(Rc::new(RefCell::new(globals)))],
+            locals: HashMap::new(),
+            functions: Vec::new(),
+            generators: Vec::new(),
             break_requested: false,
+            continue_requested: false,
+            returned: None,
             loop_depth: 0,
+            warn_non_exhaustive: false,
+            warned: HashSet::new(),
+            clock: Box::new(SystemClock::default()),
+            rng: Rng::default(),
+            exit_code: None,
         }
     }
+    // binds the command line arguments after the script to the args global
+    pub fn set_args(&mut self, args: Vec<String>) {
+        let args = args.into_iter().map(TermValue::StringValue).collect();
+        self.state[0]
+            .variables
+            .borrow_mut()
+            .insert(syslib::ARGS.to_string(), TermValue::List(Rc::new(args)));
+    }
+    pub(crate) fn function_name(&self, fidx: usize) -> &str {
+        &self.functions[fidx].name
+    }
     pub fn value(&self) -> &TermValue {
         &self.state[self.state.len() - 1].return_value
     }
+    // finds a variable, searching from the innermost scope outwards
+    // returns the index of the scope it was found in as well as its value
+    fn lookup(&self, id: &str) -> Option<(usize, TermValue)> {
+        for i in (0..self.state.len()).rev() {
+            if let Some(val) = self.state[i].variables.borrow().get(id) {
+                return Some((i, val.clone()));
+            }
+        }
+        None
+    }
+
+    // the index of the scope that holds the variable referenced at token,
+    // the one the resolver picked if it is there, else the innermost that has it
+    // (a pattern name the resolver declared may turn out to be an enum variant)
+    fn scope_of(&self, token: isize, id: &str) -> Option<usize> {
+        let has = |i: usize| self.state[i].variables.borrow().contains_key(id);
+        match self.locals.get(&token) {
+            Some(&i) if i < self.state.len() && has(i) => Some(i),
+            _ => (0..self.state.len()).rev().find(|&i| has(i)),
+        }
+    }
+
+    // the value of the variable referenced at token
+    fn lookup_at(&self, token: isize, id: &str) -> Option<TermValue> {
+        let i = self.scope_of(token, id)?;
+        self.state[i].variables.borrow().get(id).cloned()
+    }
+
+    // sets the existing variable referenced at token
+    fn assign_at(&mut self, token: isize, id: &str, val: TermValue) -> TermValue {
+        match self.scope_of(token, id) {
+            Some(i) => {
+                self.state[i]
+                    .variables
+                    .borrow_mut()
+                    .insert(id.to_string(), val.clone());
+                val
+            }
+            None => TermValue::Error(format!("Variable {} not found", id)),
+        }
+    }
+
+    // pairs the names of a list pattern with the elements of value,
+    // the rest name gets a list of whatever elements are left over
+    fn destructure(
+        &self,
+        pattern: &ListPatternContextAll<'a>,
+        value: &TermValue,
+    ) -> Result<Vec<(String, TermValue)>, TermValue> {
+        let TermValue::List(items) = value else {
+            return Err(TermValue::Error(format!(
+                "cannot destructure {:?}, expected a list",
+                value
+            )));
+        };
+        let names = pattern.IDENTIFIER_all();
+        let rest = pattern.restParameter();
+        if items.len() < names.len() || (rest.is_none() && items.len() > names.len()) {
+            let expected = if rest.is_some() {
+                format!("at least {}", names.len())
+            } else {
+                format!("{}", names.len())
+            };
+            return Err(TermValue::Error(format!(
+                "cannot destructure a list of {} elements, expected {}",
+                items.len(),
+                expected
+            )));
+        }
+        let mut bound: Vec<(String, TermValue)> = names
+            .iter()
+            .zip(items.iter())
+            .map(|(name, item)| (name.get_text(), item.clone()))
+            .collect();
+        if let Some(rest) = rest {
+            let extra = items[names.len()..].to_vec();
+            bound.push((
+                rest.IDENTIFIER().unwrap().get_text(),
+                TermValue::List(Rc::new(extra)),
+            ));
+        }
+        Ok(bound)
+    }
+
+    // calls a function or native function value with already evaluated arguments
+    // named arguments are only accepted by functions declared in lox
+    pub fn call_value(
+        &mut self,
+        callee: &TermValue,
+        args: Vec<TermValue>,
+        named: Vec<(String, TermValue)>,
+    ) -> TermValue {
+        match callee {
+            TermValue::Function(fidx) => self.call_function(*fidx, args, named),
+            TermValue::Native(native) => {
+                if !named.is_empty() {
+                    return TermValue::Error(format!(
+                        "{} does not take named arguments",
+                        native.name
+                    ));
+                }
+                if args.len() < native.min_args || args.len() > native.max_args {
+                    let max = (native.max_args != usize::MAX).then_some(native.max_args);
+                    return arity_error(native.name, native.min_args, max, args.len());
+                }
+                (native.func)(self, &args)
+            }
+            TermValue::Constructor(def, variant) => construct(def, *variant, args, named),
+            _ => TermValue::Error(format!("{:?} is not a function", callee)),
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        fidx: usize,
+        args: Vec<TermValue>,
+        mut named: Vec<(String, TermValue)>,
+    ) -> TermValue {
+        let name = self.functions[fidx].name.clone();
+        let body = self.functions[fidx].body.clone();
+        let is_generator = self.functions[fidx].is_generator;
+        let (params, rest) = match &self.functions[fidx].params {
+            Some(p) => (
+                p.params.clone(),
+                p.rest.as_ref().map(|r| r.IDENTIFIER().unwrap().get_text()),
+            ),
+            None => (Vec::new(), None),
+        };
+        let required = params.iter().filter(|p| p.value.is_none()).count();
+        let max = if rest.is_some() {
+            None
+        } else {
+            Some(params.len())
+        };
+        if rest.is_none() && args.len() > params.len() {
+            return arity_error(&name, required, max, args.len() + named.len());
+        }
+
+        // the call runs on the scopes around the declaration, not the caller's,
+        // every way out of it puts the caller's scopes back
+        let closure = self.functions[fidx]
+            .closure
+            .iter()
+            .cloned()
+            .map(ExecutionState::with_scope)
+            .collect();
+        let caller = std::mem::replace(&mut self.state, closure);
+        self.state.push(ExecutionState::new());
+        // bind parameters left to right so that a default value can refer
+        // to the parameters before it
+        for (i, param) in params.iter().enumerate() {
+            let pname = param.IDENTIFIER().unwrap().get_text();
+            let from_named = named.iter().position(|(n, _)| *n == pname);
+            let val = if i < args.len() {
+                if from_named.is_some() {
+                    self.state = caller;
+                    return TermValue::Error(format!(
+                        "{} got multiple values for argument {}",
+                        name, pname
+                    ));
+                }
+                args[i].clone()
+            } else if let Some(n) = from_named {
+                named.remove(n).1
+            } else if let Some(default) = param.value.as_ref() {
+                let val = self.visit(&*default.as_ref());
+                if let TermValue::Error(_) = val {
+                    self.state = caller;
+                    return val;
+                }
+                val
+            } else {
+                self.state = caller;
+                return arity_error(&name, required, max, args.len() + named.len());
+            };
+            let top = self.state.len() - 1;
+            self.state[top].variables.borrow_mut().insert(pname, val);
+        }
+        if let Some((n, _)) = named.first() {
+            self.state = caller;
+            return TermValue::Error(format!("{} has no parameter named {}", name, n));
+        }
+        if let Some(rest) = rest {
+            let extra = args.iter().skip(params.len()).cloned().collect();
+            let top = self.state.len() - 1;
+            self.state[top]
+                .variables
+                .borrow_mut()
+                .insert(rest, TermValue::List(Rc::new(extra)));
+        }
+
+        if is_generator {
+            // nothing runs until the first value is asked for
+            let frames = std::mem::replace(&mut self.state, caller);
+            return self.new_generator(frames, body);
+        }
+        let mut result = TermValue::Nil;
+        for node in body.get_children() {
+            result = self.visit(node.as_ref());
+            if let TermValue::Error(_) = result {
+                break;
+            }
+            if self.returned.is_some() {
+                break;
+            }
+        }
+        self.state = caller;
+        match result {
+            TermValue::Error(_) => result,
+            // falling off the end of the body returns nil
+            _ => self.returned.take().unwrap_or(TermValue::Nil),
+        }
+    }
+
+    // picks the arm of a match statement to run, returning the scope that holds
+    // the arm's bindings together with its body, or None if no arm matches
+    pub(crate) fn select_arm(
+        &mut self,
+        ctx: &MatchStmtContext<'a>,
+    ) -> Result<Option<(ExecutionState, Rc<StatementContextAll<'a>>)>, TermValue> {
+        let subject = self.visit(&*ctx.subject.as_ref().unwrap().as_ref());
+        if let TermValue::Error(_) = subject {
+            return Err(subject);
+        }
+        if self.warn_non_exhaustive {
+            self.check_exhaustive(ctx, &subject);
+        }
+        for arm in ctx.matchArm_all() {
+            let mut bindings = HashMap::new();
+            let matched = self.match_pattern(&arm.pattern().unwrap(), &subject, &mut bindings);
+            match matched {
+                TermValue::Error(_) => return Err(matched),
+                TermValue::False => continue,
+                _ => {}
+            }
+            // bindings are visible to the guard and the body of the arm
+            self.state
+                .push(ExecutionState::with_scope(Rc::new(RefCell::new(bindings))));
+            if let Some(guard) = arm.guard.as_ref() {
+                let cond = self.visit(&*guard.as_ref());
+                match cond {
+                    TermValue::True => {}
+                    TermValue::Error(_) => {
+                        self.state.pop();
+                        return Err(cond);
+                    }
+                    _ => {
+                        self.state.pop();
+                        continue;
+                    }
+                }
+            }
+            let scope = self.state.pop().unwrap();
+            return Ok(Some((scope, arm.body.clone().unwrap())));
+        }
+        Ok(None)
+    }
+
+    // the value of a variant without fields, if that is what id names
+    fn unit_variant(&self, id: &str) -> Option<Rc<EnumValue>> {
+        match self.lookup(id) {
+            Some((_, TermValue::Enum(e))) if e.fields.is_empty() && e.name() == id => Some(e),
+            _ => None,
+        }
+    }
+
+    // returns True if the subject matches one of the alternatives of the pattern
+    // a bare identifier matches a variant without fields of that name, one that
+    // names a variant with fields is an error, any other identifier matches and
+    // binds the subject to that name
+    fn match_pattern(
+        &mut self,
+        pattern: &PatternContextAll<'a>,
+        subject: &TermValue,
+        bindings: &mut HashMap<String, TermValue>,
+    ) -> TermValue {
+        for atom in pattern.alternatives.iter() {
+            if let Some(id) = atom.IDENTIFIER() {
+                let id = id.get_text();
+                if let Some(unit) = self.unit_variant(&id) {
+                    if *subject == TermValue::Enum(unit) {
+                        return TermValue::True;
+                    }
+                    continue;
+                }
+                // a variant with fields needs them spelled out, Circle(r), rather
+                // than silently binding a new name that matches everything
+                if let Some((_, TermValue::Constructor(def, idx))) = self.lookup(&id) {
+                    return located_error(
+                        &**atom,
+                        format!(
+                            "{} takes {} fields, match it as {}({})",
+                            id,
+                            def.variants[idx].1.len(),
+                            id,
+                            def.variants[idx].1.join(", ")
+                        ),
+                    );
+                }
+                bindings.insert(id, subject.clone());
+                return TermValue::True;
+            }
+            if let Some(variant) = atom.variantPattern() {
+                let ids = variant.IDENTIFIER_all();
+                let name = ids[0].get_text();
+                let (def, idx) = match self.lookup(&name) {
+                    Some((_, TermValue::Constructor(def, idx))) => (def, idx),
+                    _ => return TermValue::Error(format!("{} is not an enum variant", name)),
+                };
+                if ids.len() - 1 != def.variants[idx].1.len() {
+                    return TermValue::Error(format!(
+                        "{} has {} fields, pattern has {}",
+                        name,
+                        def.variants[idx].1.len(),
+                        ids.len() - 1
+                    ));
+                }
+                if let TermValue::Enum(e) = subject {
+                    if Rc::ptr_eq(&e.def, &def) && e.variant == idx {
+                        for (id, val) in ids[1..].iter().zip(e.fields.iter()) {
+                            bindings.insert(id.get_text(), val.clone());
+                        }
+                        return TermValue::True;
+                    }
+                }
+                continue;
+            }
+            // a list pattern matches a list it can destructure, [a, b] only
+            // lists of two elements and [first, ...rest] any list that is not empty
+            if let Some(list) = atom.listPattern() {
+                if let Ok(bound) = self.destructure(&list, subject) {
+                    bindings.extend(bound);
+                    return TermValue::True;
+                }
+                continue;
+            }
+            let low = self.visit(&*atom.low.as_ref().unwrap().as_ref());
+            if atom.high.is_none() {
+                if *subject == low {
+                    return TermValue::True;
+                }
+                continue;
+            }
+            let high = self.visit(&*atom.high.as_ref().unwrap().as_ref());
+            match (subject, low, high) {
+                (TermValue::Number(x), TermValue::Number(l), TermValue::Number(h)) => {
+                    let below_high = if atom.DOTDOTEQ().is_some() {
+                        *x <= h
+                    } else {
+                        *x < h
+                    };
+                    if *x >= l && below_high {
+                        return TermValue::True;
+                    }
+                }
+                (_, TermValue::Number(_), TermValue::Number(_)) => {}
+                _ => {
+                    return TermValue::Error("range pattern bounds must be numbers".to_string());
+                }
+            }
+        }
+        TermValue::False
+    }
+
+    // warn (once per statement) when a match on a boolean or an enum value
+    // leaves some of its possible values without an unguarded arm
+    fn check_exhaustive(&mut self, ctx: &MatchStmtContext<'a>, subject: &TermValue) {
+        let (kind, mut missing) = match subject {
+            TermValue::True | TermValue::False => (
+                "a boolean".to_string(),
+                vec!["true".to_string(), "false".to_string()],
+            ),
+            TermValue::Enum(e) => (
+                e.def.name.clone(),
+                e.def
+                    .variants
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+            ),
+            _ => return,
+        };
+        let (line, column) = (ctx.start().get_line(), ctx.start().get_column());
+        if !self.warned.insert((line, column)) {
+            return;
+        }
+        for arm in ctx.matchArm_all() {
+            if arm.guard.is_some() {
+                continue;
+            }
+            for atom in arm.pattern().unwrap().alternatives.iter() {
+                let covered = if let Some(id) = atom.IDENTIFIER() {
+                    // anything but a variant name binds, so every value is covered
+                    if self.unit_variant(&id.get_text()).is_none() {
+                        return;
+                    }
+                    id.get_text()
+                } else if let Some(variant) = atom.variantPattern() {
+                    variant.IDENTIFIER_all()[0].get_text()
+                } else if atom.low.is_some() && atom.high.is_none() {
+                    atom.low.as_ref().unwrap().get_text()
+                } else {
+                    continue;
+                };
+                missing.retain(|name| *name != covered);
+            }
+        }
+        if !missing.is_empty() {
+            eprintln!(
+                "warning: line {}:{} match on {} does not cover {}",
+                line,
+                column,
+                kind,
+                missing.join(", ")
+            );
+        }
+    }
+}
+// builds an enum value from the arguments of a call to one of its constructors
+fn construct(
+    def: &Rc<EnumDef>,
+    variant: usize,
+    args: Vec<TermValue>,
+    named: Vec<(String, TermValue)>,
+) -> TermValue {
+    let (name, field_names) = &def.variants[variant];
+    if args.len() > field_names.len() {
+        let n = field_names.len();
+        return arity_error(name, n, Some(n), args.len() + named.len());
+    }
+    let mut fields: Vec<Option<TermValue>> = args.into_iter().map(Some).collect();
+    fields.resize(field_names.len(), None);
+    for (field, val) in named {
+        let Some(i) = field_names.iter().position(|f| *f == field) else {
+            return TermValue::Error(format!("{} has no field named {}", name, field));
+        };
+        if fields[i].is_some() {
+            return TermValue::Error(format!("{} got multiple values for field {}", name, field));
+        }
+        fields[i] = Some(val);
+    }
+    if let Some(i) = fields.iter().position(|f| f.is_none()) {
+        return TermValue::Error(format!("{} is missing field {}", name, field_names[i]));
+    }
+    TermValue::Enum(Rc::new(EnumValue {
+        def: def.clone(),
+        variant,
+        fields: fields.into_iter().flatten().collect(),
+    }))
+}
+// an error that points at where ctx starts in the source
+fn located_error(ctx: &dyn LoxParserContext<'_>, msg: String) -> TermValue {
+    let (line, column) = (ctx.start().get_line(), ctx.start().get_column());
+    TermValue::Error(format!("line {}:{} {}", line, column, msg))
+}
+// a loop condition that is neither true nor false
+pub(crate) fn condition_error(ctx: &dyn LoxParserContext<'_>, cond: &TermValue) -> TermValue {
+    located_error(
+        ctx,
+        format!(
+            "loop condition must be a bool, got {}",
+            natives::type_name(cond)
+        ),
+    )
+}
+// an arithmetic operator applied to something other than two numbers
+fn operand_error(
+    ctx: &dyn LoxParserContext<'_>,
+    op: &str,
+    left: &TermValue,
+    right: &TermValue,
+) -> TermValue {
+    located_error(
+        ctx,
+        format!(
+            "operands of {} must be numbers, got {} and {}",
+            op,
+            natives::type_name(left),
+            natives::type_name(right)
+        ),
+    )
+}
+fn arity_error(name: &str, min: usize, max: Option<usize>, got: usize) -> TermValue {
+    let expected = match max {
+        Some(max) if max == min => format!("{}", min),
+        Some(max) => format!("{} to {}", min, max),
+        None => format!("at least {}", min),
+    };
+    TermValue::Error(format!(
+        "{} expects {} arguments, got {}",
+        name, expected, got
+    ))
 }
 impl<'a> ParseTreeVisitorCompat<'a> for InterpVisit<'a> {
     type Node = LoxParserContextType;
@@ -69,7 +645,6 @@ impl<'a> ParseTreeVisitorCompat<'a> for InterpVisit<'a> {
         next
     }
     fn visit_error_node(&mut self, node: &ErrorNode<'_, Self::Node>) -> Self::Return {
-        println!("visit_error_node");
         TermValue::Error(node.get_text())
     }
 }
@@ -84,6 +659,38 @@ pub enum TermValue {
     Empty,
     Error(String),
     Function(usize),
+    Native(NativeFunction),
+    Range(RangeValue),
+    List(Rc<Vec<TermValue>>),
+    Generator(Rc<GeneratorRef>),
+    Enum(Rc<EnumValue>),
+    // the constructor function of an enum variant that has fields
+    Constructor(Rc<EnumDef>, usize),
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeValue {
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+    pub inclusive: bool,
+}
+#[derive(Debug)]
+pub struct EnumDef {
+    pub name: String,
+    // variant name and the names of its fields
+    pub variants: Vec<(String, Vec<String>)>,
+}
+// enum values are equal when they are the same variant of the same enum with equal fields
+#[derive(Debug)]
+pub struct EnumValue {
+    pub def: Rc<EnumDef>,
+    pub variant: usize,
+    pub fields: Vec<TermValue>,
+}
+impl EnumValue {
+    pub fn name(&self) -> &str {
+        &self.def.variants[self.variant].0
+    }
 }
 impl PartialEq for TermValue {
     fn eq(&self, other: &Self) -> bool {
@@ -96,6 +703,14 @@ impl PartialEq for TermValue {
             (Self::Empty, Self::Empty) => true,
             (Self::Error(a), Self::Error(b)) => a == b,
             (Self::Function(_), Self::Function(_)) => true,
+            (Self::Native(a), Self::Native(b)) => a.name == b.name,
+            (Self::Range(a), Self::Range(b)) => a == b,
+            (Self::List(a), Self::List(b)) => a == b,
+            (Self::Generator(a), Self::Generator(b)) => Rc::ptr_eq(a, b),
+            (Self::Enum(a), Self::Enum(b)) => {
+                Rc::ptr_eq(&a.def, &b.def) && a.variant == b.variant && a.fields == b.fields
+            }
+            (Self::Constructor(a, i), Self::Constructor(b, j)) => Rc::ptr_eq(a, b) && i == j,
             _ => false,
         }
     }
@@ -117,40 +732,31 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
     fn visit_printStmt(&mut self, ctx: &PrintStmtContext<'a>) -> Self::Return {
         trace!("visit_printStmt {:?}", ctx.get_text());
         let res = self.visit(&*ctx.exp.as_ref().unwrap().as_ref());
-        match &res {
-            TermValue::Error(_) => {
-                return res;
-            }
-            TermValue::Nil => {
-                println!("nil");
-            }
-            TermValue::True => {
-                println!("true");
-            }
-            TermValue::False => {
-                println!("false");
-            }
-            TermValue::Number(x) => {
-                println!("{}", x);
-            }
-            TermValue::StringValue(x) => {
-                println!("{}", x);
-            }
-            _ => {
-                println!("unknown");
-            }
+        if let TermValue::Error(_) = res {
+            return res;
         }
+        println!("{}", self.display(&res));
         res
     }
     fn visit_varDecl(&mut self, ctx: &VarDeclContext<'a>) -> Self::Return {
         trace!("visit_varDecl {:?}", ctx.get_text());
-        let id = ctx.IDENTIFIER().unwrap().get_text();
-        let val = self.visit(&*ctx.expr.as_ref().unwrap().as_ref());
+        // a declaration without an initializer starts out as nil
+        let val = match ctx.expr.as_ref() {
+            Some(expr) => self.visit(&*expr.as_ref()),
+            None => TermValue::Nil,
+        };
         if let TermValue::Error(_) = val {
             return val;
         }
+        let bound = match ctx.target.as_ref() {
+            Some(target) => match self.destructure(target, &val) {
+                Ok(bound) => bound,
+                Err(err) => return err,
+            },
+            None => vec![(ctx.IDENTIFIER().unwrap().get_text(), val)],
+        };
         let top = self.state.len() - 1;
-        self.state[top].variables.insert(id.clone(), val.clone());
+        self.state[top].variables.borrow_mut().extend(bound);
         TermValue::Empty
     }
     fn visit_functionDecl(
@@ -160,22 +766,56 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
         trace!("visit_functionDecl {:?}", ctx.get_text());
         let top = self.state.len() - 1;
         let id = ctx.IDENTIFIER().unwrap().get_text();
-        let val = self.visit(&*ctx.body.as_ref().unwrap().as_ref());
-        if let Some(ref b) = ctx.body {
-            self.state[top].functions.push(b.clone());
-        }
-        // self.state[top].functions.push(*ctx.body.unwrap().clone());
-        // if let TermValue::Error(_) = val {
-        //     return val;
-        // }
-        let fidx = self.state[top].functions.len() - 1;
+        let body = ctx.body.as_ref().unwrap().clone();
+        self.functions.push(FunctionDef {
+            name: id.clone(),
+            params: ctx.parameters(),
+            is_generator: generator::contains_yield(&*body),
+            body,
+            closure: self.state.iter().map(|s| s.variables.clone()).collect(),
+        });
+        let fidx = self.functions.len() - 1;
         self.state[top]
             .variables
+            .borrow_mut()
             .insert(id.clone(), TermValue::Function(fidx));
-
-        let f = self.state[top].functions[fidx].clone();
-
-        let val = self.visit(f.as_ref());
+        TermValue::Empty
+    }
+    fn visit_enumDecl(&mut self, ctx: &EnumDeclContext<'a>) -> Self::Return {
+        trace!("visit_enumDecl {:?}", ctx.get_text());
+        let variants = ctx
+            .enumVariant_all()
+            .iter()
+            .map(|variant| {
+                let ids: Vec<String> = variant
+                    .IDENTIFIER_all()
+                    .iter()
+                    .map(|id| id.get_text())
+                    .collect();
+                (ids[0].clone(), ids[1..].to_vec())
+            })
+            .collect();
+        let def = Rc::new(EnumDef {
+            name: ctx.IDENTIFIER().unwrap().get_text(),
+            variants,
+        });
+        // a variant with fields is bound to its constructor, one without to its only value
+        let top = self.state.len() - 1;
+        for (i, (name, fields)) in def.variants.iter().enumerate() {
+            let val = if fields.is_empty() {
+                TermValue::Enum(Rc::new(EnumValue {
+                    def: def.clone(),
+                    variant: i,
+                    fields: Vec::new(),
+                }))
+            } else {
+                TermValue::Constructor(def.clone(), i)
+            };
+            self.state[top]
+                .variables
+                .borrow_mut()
+                .insert(name.clone(), val);
+        }
         TermValue::Empty
     }
     fn visit_block(&mut self, ctx: &antlr::loxparser::BlockContext<'a>) -> Self::Return {
@@ -189,6 +829,11 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
             if let TermValue::Error(_) = result {
                 return result;
             }
+            // skip the rest of the block if a loop is being exited or restarted
+            // or the function is returning
+            if self.break_requested || self.continue_requested || self.returned.is_some() {
+                break;
+            }
         }
         self.state.pop();
         result
@@ -196,10 +841,8 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
     fn visit_identifier(&mut self, ctx: &IdentifierContext<'a>) -> Self::Return {
         trace!("visit_identifier {:?}", ctx.get_text());
         let id = ctx.get_text();
-        for i in (0..self.state.len()).rev() {
-            if let Some(val) = self.state[i].variables.get(&id) {
-                return val.clone();
-            }
+        if let Some(val) = self.lookup_at(ctx.start().get_token_index(), &id) {
+            return val;
         }
         // if let Some(val) = self.state[self.state.len() - 1].variables.get(&id) {
         //     return val.clone();
@@ -235,12 +878,13 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
                 }
                 TermValue::True => {
                     result = self.visit(&*ctx.body.as_ref().unwrap().as_ref());
-                    if self.break_requested {
+                    if self.break_requested || self.returned.is_some() {
                         self.break_requested = false;
                         self.loop_depth -= 1;
 
                         break;
                     }
+                    self.continue_requested = false;
                     if let TermValue::Error(_) = result {
                         self.loop_depth -= 1;
 
@@ -252,7 +896,11 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
 
                     break;
                 }
-                _ => unreachable!(),
+                _ => {
+                    self.loop_depth -= 1;
+
+                    return condition_error(&**ctx.condition.as_ref().unwrap(), &cond);
+                }
             }
         }
         result
@@ -271,21 +919,30 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
     fn visit_forStmt(&mut self, ctx: &antlr::loxparser::ForStmtContext<'a>) -> Self::Return {
         trace!("visit_forStmt {:?}", ctx.get_text());
         let mut result = Self::Return::default();
-        if ctx.forvar.is_some() {
-            self.visit(&*ctx.forvar.as_ref().unwrap().as_ref());
+        // the loop variable lives in its own scope around the loop
+        self.state.push(ExecutionState::new());
+        let init = if ctx.forvar.is_some() {
+            self.visit(&*ctx.forvar.as_ref().unwrap().as_ref())
         } else if ctx.initializer.is_some() {
-            self.visit(&*ctx.initializer.as_ref().unwrap().as_ref());
+            self.visit(&*ctx.initializer.as_ref().unwrap().as_ref())
+        } else {
+            TermValue::Empty
+        };
+        if let TermValue::Error(_) = init {
+            self.state.pop();
+            return init;
         }
         loop {
             self.loop_depth += 1;
             // defer! {
             //     self.loop_depth -= 1;
             // }
-            if let Some(cond) = ctx.condition.as_ref() {
-                let cond = self.visit(&*cond.as_ref());
+            if let Some(condition) = ctx.condition.as_ref() {
+                let cond = self.visit(&*condition.as_ref());
                 match cond {
                     TermValue::Error(_) => {
                         self.loop_depth -= 1;
+                        self.state.pop();
                         return cond;
                     }
                     TermValue::True => {}
@@ -293,27 +950,136 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
                         self.loop_depth -= 1;
                         break;
                     }
-                    _ => unreachable!(),
+                    _ => {
+                        self.loop_depth -= 1;
+                        self.state.pop();
+                        return condition_error(&**condition, &cond);
+                    }
                 }
             }
             result = self.visit(&*ctx.body.as_ref().unwrap().as_ref());
-            if self.break_requested {
+            if self.break_requested || self.returned.is_some() {
                 self.break_requested = false;
                 self.loop_depth -= 1;
 
                 break;
             }
+            self.continue_requested = false;
             if let TermValue::Error(_) = result {
                 self.loop_depth -= 1;
+                self.state.pop();
                 return result;
             }
             if ctx.increment.is_some() {
-                self.visit(&*ctx.increment.as_ref().unwrap().as_ref());
+                let step = self.visit(&*ctx.increment.as_ref().unwrap().as_ref());
+                if let TermValue::Error(_) = step {
+                    self.loop_depth -= 1;
+                    self.state.pop();
+                    return step;
+                }
             }
         }
-
+        self.state.pop();
+        result
+    }
+    fn visit_matchStmt(&mut self, ctx: &MatchStmtContext<'a>) -> Self::Return {
+        trace!("visit_matchStmt {:?}", ctx.get_text());
+        match self.select_arm(ctx) {
+            Err(err) => err,
+            Ok(None) => TermValue::Empty,
+            Ok(Some((scope, body))) => {
+                self.state.push(scope);
+                let result = self.visit(&*body);
+                self.state.pop();
+                result
+            }
+        }
+    }
+    fn visit_yieldStmt(&mut self, ctx: &YieldStmtContext<'a>) -> Self::Return {
+        // yields inside a generator are run by the generator, never visited
+        trace!("visit_yieldStmt {:?}", ctx.get_text());
+        TermValue::Error("yield outside of a generator".to_string())
+    }
+    fn visit_returnStmt(&mut self, ctx: &ReturnStmtContext<'a>) -> Self::Return {
+        trace!("visit_returnStmt {:?}", ctx.get_text());
+        let val = match ctx.value.as_ref() {
+            Some(value) => self.visit(&*value.as_ref()),
+            None => TermValue::Nil,
+        };
+        if let TermValue::Error(_) = val {
+            return val;
+        }
+        // the statements around it stop until call_function takes the value
+        self.returned = Some(val);
+        TermValue::Empty
+    }
+    fn visit_literal(&mut self, ctx: &LiteralContext<'a>) -> Self::Return {
+        trace!("visit_literal {:?}", ctx.get_text());
+        if let Some(num) = ctx.NUMBER() {
+            let x = match number::parse_number(&num.get_text()) {
+                Ok(x) => x,
+                Err(msg) => return located_error(ctx, msg),
+            };
+            return TermValue::Number(if ctx.MINUS().is_some() { -x } else { x });
+        }
+        if let Some(s) = ctx.STRING() {
+            let str = s.get_text();
+            let str = str.strip_prefix('"').unwrap().strip_suffix('"').unwrap();
+            return TermValue::StringValue(str.to_string());
+        }
+        match ctx.get_text().as_str() {
+            "true" => TermValue::True,
+            "false" => TermValue::False,
+            _ => TermValue::Nil,
+        }
+    }
+    fn visit_forInStmt(&mut self, ctx: &ForInStmtContext<'a>) -> Self::Return {
+        trace!("visit_forInStmt {:?}", ctx.get_text());
+        let id = ctx.IDENTIFIER().unwrap().get_text();
+        let iterable = self.visit(&*ctx.iterable.as_ref().unwrap().as_ref());
+        if let TermValue::Error(_) = iterable {
+            return iterable;
+        }
+        let mut iter = match iterator::iterate(&iterable) {
+            Ok(iter) => iter,
+            Err(err) => return err,
+        };
+        let mut result = Self::Return::default();
+        self.loop_depth += 1;
+        while let Some(item) = iter.next_item(self) {
+            if let TermValue::Error(_) = item {
+                result = item;
+                break;
+            }
+            // every iteration gets a fresh scope holding the loop variable
+            let scope = ExecutionState::new();
+            scope.variables.borrow_mut().insert(id.clone(), item);
+            self.state.push(scope);
+            result = self.visit(&*ctx.body.as_ref().unwrap().as_ref());
+            self.state.pop();
+            if self.break_requested || self.returned.is_some() {
+                self.break_requested = false;
+                break;
+            }
+            self.continue_requested = false;
+            if let TermValue::Error(_) = result {
+                break;
+            }
+        }
+        self.loop_depth -= 1;
         result
     }
+    fn visit_continueStmt(
+        &mut self,
+        ctx: &antlr::loxparser::ContinueStmtContext<'a>,
+    ) -> Self::Return {
+        trace!("visit_continueStmt {:?}", ctx.get_text());
+        if self.loop_depth == 0 {
+            return TermValue::Error("continue outside of loop".to_string());
+        }
+        self.continue_requested = true;
+        TermValue::Empty
+    }
     fn visit_breakStmt(&mut self, ctx: &antlr::loxparser::BreakStmtContext<'a>) -> Self::Return {
         trace!("visit_breakStmt {:?}", ctx.get_text());
         if self.loop_depth == 0 {
@@ -336,19 +1102,37 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
 
     fn visit_assignment_alt(&mut self, ctx: &Assignment_altContext<'a>) -> TermValue {
         trace!("visit_assignment_alt {:?}", ctx.get_text());
-        let id = ctx.IDENTIFIER().unwrap().get_text();
+        let id = ctx.IDENTIFIER().unwrap();
         let val = self.visit(&*ctx.iter.as_ref().unwrap().as_ref());
         if let TermValue::Error(_) = val {
             return val;
         }
-
-        for i in (0..self.state.len()).rev() {
-            if let Some(old_val) = self.state[i].variables.get_mut(&id) {
-                *old_val = val.clone();
-                return val;
+        self.assign_at(id.symbol.get_token_index(), &id.get_text(), val)
+    }
+    fn visit_destructure_alt(&mut self, ctx: &Destructure_altContext<'a>) -> TermValue {
+        trace!("visit_destructure_alt {:?}", ctx.get_text());
+        let val = self.visit(&*ctx.value.as_ref().unwrap().as_ref());
+        if let TermValue::Error(_) = val {
+            return val;
+        }
+        let target = ctx.target.as_ref().unwrap();
+        let bound = match self.destructure(target, &val) {
+            Ok(bound) => bound,
+            Err(err) => return err,
+        };
+        // the tokens of the names, in the order destructure binds them
+        let tokens = target
+            .IDENTIFIER_all()
+            .into_iter()
+            .chain(target.restParameter().and_then(|rest| rest.IDENTIFIER()))
+            .map(|id| id.symbol.get_token_index());
+        for ((id, item), token) in bound.into_iter().zip(tokens) {
+            let res = self.assign_at(token, &id, item);
+            if let TermValue::Error(_) = res {
+                return res;
             }
         }
-        return TermValue::Error(format!("Variable {} not found", id));
+        val
     }
     fn visit_logic_or(&mut self, ctx: &Logic_orContext<'a>) -> TermValue {
         trace!("visit_logic_or {:?}", ctx.get_text());
@@ -371,25 +1155,37 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
 
     fn visit_callfun(&mut self, ctx: &antlr::loxparser::CallfunContext<'a>) -> Self::Return {
         trace!("visit_callfun {:?}", ctx.get_text());
-        let id = ctx.id.as_ref().unwrap().get_text();
-        let top = self.state.len() - 1;
-        let fidx = match self.state[top].variables.get(&id) {
-            Some(TermValue::Function(fidx)) => *fidx,
+        let primary = ctx.id.as_ref().unwrap();
+        let id = primary.get_text();
+        let callee = match self.lookup_at(primary.start().get_token_index(), &id) {
+            Some(
+                val @ (TermValue::Function(_) | TermValue::Native(_) | TermValue::Constructor(..)),
+            ) => val,
             _ => {
                 return TermValue::Error(format!("Function {} not found", id));
             }
         };
-        let f = self.state[top].functions[fidx].clone();
-        let mut result = Self::Return::default();
-        self.state.push(ExecutionState::new());
-        for node in f.get_children() {
-            result = self.visit(node.as_ref());
-            if let TermValue::Error(_) = result {
-                return result;
+        let mut args = Vec::new();
+        let mut named = Vec::new();
+        if let Some(arguments) = ctx.arguments() {
+            for arg in arguments.argument_all() {
+                let val = self.visit(&*arg.value.as_ref().unwrap().as_ref());
+                if let TermValue::Error(_) = val {
+                    return val;
+                }
+                if let Some(name) = arg.IDENTIFIER() {
+                    named.push((name.get_text(), val));
+                } else if !named.is_empty() {
+                    return TermValue::Error(format!(
+                        "{}: positional argument after named argument",
+                        id
+                    ));
+                } else {
+                    args.push(val);
+                }
             }
         }
-        self.state.pop();
-        result
+        self.call_value(&callee, args, named)
     }
     fn visit_logic_and(&mut self, ctx: &Logic_andContext<'a>) -> TermValue {
         trace!("visit_logic_and {:?}", ctx.get_text());
@@ -480,6 +1276,29 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
         }
         TermValue::Error("must both be numbers".to_string())
     }
+    fn visit_rangeExpr(&mut self, ctx: &RangeExprContext<'a>) -> TermValue {
+        trace!("visit_rangeExpr {:?}", ctx.get_text());
+        let left = self.visit(&*ctx.left.as_ref().unwrap().as_ref());
+        if let TermValue::Error(_) = left {
+            return left;
+        }
+        if ctx.right.is_none() {
+            return left;
+        }
+        let right = self.visit(&*ctx.right.as_ref().unwrap().as_ref());
+        if let TermValue::Error(_) = right {
+            return right;
+        }
+        if let (TermValue::Number(start), TermValue::Number(end)) = (left, right) {
+            return TermValue::Range(RangeValue {
+                start,
+                end,
+                step: 1.0,
+                inclusive: ctx.DOTDOTEQ().is_some(),
+            });
+        }
+        TermValue::Error("range bounds must be numbers".to_string())
+    }
     fn visit_term(&mut self, ctx: &antlr::loxparser::TermContext<'a>) -> TermValue {
         trace!("visit_term {:?}", ctx.get_text());
         let left = self.visit(&*ctx.left.as_ref().unwrap().as_ref());
@@ -497,16 +1316,14 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
         if let TermValue::Error(_) = right {
             return right;
         }
-        if let TermValue::Number(l) = left {
-            if let TermValue::Number(r) = right {
-                if ctx.PLUS(0).is_some() {
-                    return TermValue::Number(l + r);
-                } else if ctx.MINUS(0).is_some() {
-                    return TermValue::Number(l - r);
-                }
-            }
+        let op = if ctx.PLUS(0).is_some() { "+" } else { "-" };
+        if let (TermValue::Number(l), TermValue::Number(r)) = (&left, &right) {
+            return match op {
+                "+" => TermValue::Number(l + r),
+                _ => TermValue::Number(l - r),
+            };
         }
-        panic!("x");
+        operand_error(ctx, op, &left, &right)
     }
     fn visit_factor(&mut self, ctx: &antlr::loxparser::FactorContext<'a>) -> TermValue {
         trace!("visit_factor {:?}", ctx.get_text());
@@ -521,16 +1338,14 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
         if let TermValue::Error(_) = right {
             return right;
         }
-        if let TermValue::Number(l) = left {
-            if let TermValue::Number(r) = right {
-                if ctx.STAR(0).is_some() {
-                    return TermValue::Number(l * r);
-                } else if ctx.SLASH(0).is_some() {
-                    return TermValue::Number(l / r);
-                }
-            }
+        let op = if ctx.STAR(0).is_some() { "*" } else { "/" };
+        if let (TermValue::Number(l), TermValue::Number(r)) = (&left, &right) {
+            return match op {
+                "*" => TermValue::Number(l * r),
+                _ => TermValue::Number(l / r),
+            };
         }
-        panic!("must be numbers");
+        operand_error(ctx, op, &left, &right)
     }
     fn visit_unary_alt(&mut self, ctx: &Unary_altContext<'a>) -> TermValue {
         trace!("visit_unary_alt {:?}", ctx.get_text());
@@ -548,7 +1363,13 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
             if let TermValue::Number(x) = right {
                 TermValue::Number(-x)
             } else {
-                panic!("Expected number")
+                located_error(
+                    ctx,
+                    format!(
+                        "operand of - must be a number, got {}",
+                        natives::type_name(&right)
+                    ),
+                )
             }
         } else {
             right
@@ -559,6 +1380,18 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
         let res = self.visit(ctx.expression().as_ref().unwrap().as_ref());
         res
     }
+    fn visit_list(&mut self, ctx: &ListContext<'a>) -> TermValue {
+        trace!("visit_list {:?}", ctx.get_text());
+        let mut items = Vec::new();
+        for expr in ctx.expression_all() {
+            let val = self.visit(&*expr);
+            if let TermValue::Error(_) = val {
+                return val;
+            }
+            items.push(val);
+        }
+        TermValue::List(Rc::new(items))
+    }
     fn visit_bool_false(&mut self, _ctx: &Bool_falseContext) -> TermValue {
         trace!("visit_bool_false");
         TermValue::False
@@ -570,7 +1403,10 @@ impl<'a> LoxVisitorCompat<'a> for InterpVisit<'a> {
     fn visit_number(&mut self, ctx: &NumberContext) -> TermValue {
         let text = ctx.get_text();
         trace!("visit_number {:?}", text);
-        TermValue::Number(text.parse().unwrap())
+        match number::parse_number(&text) {
+            Ok(x) => TermValue::Number(x),
+            Err(msg) => located_error(ctx, msg),
+        }
     }
     fn visit_nil(&mut self, _ctx: &NilContext) -> TermValue {
         trace!("visit_nil");
@@ -0,0 +1,121 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+use crate::natives::{native, string_arg, NativeFunction};
+
+/*
+
+    The console and file library, bound as globals.
+    A failed read or write is a runtime error that carries the message of the
+    operating system, e.g. "read_file: cannot read data.txt: No such file or
+    directory (os error 2)".
+
+*/
+pub const IO: &[NativeFunction] = &[
+    native("input", 0, 1, input),
+    native("readline", 0, 0, readline),
+    native("read_file", 1, 1, read_file),
+    native("read_lines", 1, 1, read_lines),
+    native("write_file", 2, 2, write_file),
+    native("append_file", 2, 2, append_file),
+    native("eprint", 1, 1, eprint),
+];
+
+fn io_error(name: &str, what: &str, path: &str, err: io::Error) -> TermValue {
+    TermValue::Error(format!("{}: cannot {} {}: {}", name, what, path, err))
+}
+
+// the next line of stdin without its line ending, nil at the end of the input
+fn read_stdin_line(name: &str) -> TermValue {
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) => TermValue::Nil,
+        Ok(_) => {
+            let end = line.trim_end_matches(['\r', '\n']).len();
+            line.truncate(end);
+            TermValue::StringValue(line)
+        }
+        Err(err) => TermValue::Error(format!("{}: cannot read stdin: {}", name, err)),
+    }
+}
+
+// input(prompt) shows the prompt, if any, and reads a line
+fn input(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    if !args.is_empty() {
+        let prompt = match string_arg("input", args, 0) {
+            Ok(prompt) => prompt,
+            Err(err) => return err,
+        };
+        print!("{}", prompt);
+        if let Err(err) = io::stdout().flush() {
+            return TermValue::Error(format!("input: cannot write prompt: {}", err));
+        }
+    }
+    read_stdin_line("input")
+}
+
+fn readline(_interp: &mut InterpVisit, _args: &[TermValue]) -> TermValue {
+    read_stdin_line("readline")
+}
+
+fn read_file(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let path = match string_arg("read_file", args, 0) {
+        Ok(path) => path,
+        Err(err) => return err,
+    };
+    match fs::read_to_string(path) {
+        Ok(contents) => TermValue::StringValue(contents),
+        Err(err) => io_error("read_file", "read", path, err),
+    }
+}
+
+// read_lines(path), the lines of a file as a list to loop over with for-in
+fn read_lines(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let path = match string_arg("read_lines", args, 0) {
+        Ok(path) => path,
+        Err(err) => return err,
+    };
+    match fs::read_to_string(path) {
+        Ok(contents) => TermValue::List(Rc::new(
+            contents
+                .lines()
+                .map(|line| TermValue::StringValue(line.to_string()))
+                .collect(),
+        )),
+        Err(err) => io_error("read_lines", "read", path, err),
+    }
+}
+
+// writes contents to the file at path, replacing it or adding to its end
+fn store(name: &str, args: &[TermValue], append: bool) -> TermValue {
+    let (path, contents) = match (string_arg(name, args, 0), string_arg(name, args, 1)) {
+        (Ok(path), Ok(contents)) => (path, contents),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path);
+    match file.and_then(|mut file| file.write_all(contents.as_bytes())) {
+        Ok(()) => TermValue::Nil,
+        Err(err) => io_error(name, "write", path, err),
+    }
+}
+
+fn write_file(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    store("write_file", args, false)
+}
+
+fn append_file(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    store("append_file", args, true)
+}
+
+// eprint(x) prints to stderr the way print prints to stdout
+fn eprint(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    eprintln!("{}", interp.display(&args[0]));
+    TermValue::Nil
+}
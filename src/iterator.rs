@@ -0,0 +1,88 @@
+use std::rc::Rc;
+
+use crate::generator::GeneratorRef;
+use crate::interpvisitor::{InterpVisit, RangeValue, TermValue};
+
+/*
+
+    The iterator protocol used by for-in loops.
+    Any value that can be looped over is turned into a LoxIterator by iterate(),
+    which then hands out one TermValue per step. New iterable kinds only need
+    a variant here and an arm in iterate(), the loop itself does not change.
+    next_item() gets the interpreter so that a generator can run its body
+    up to the next yield.
+
+*/
+pub enum LoxIterator {
+    Range {
+        next: f64,
+        range: RangeValue,
+    },
+    Chars {
+        chars: Vec<char>,
+        pos: usize,
+    },
+    List {
+        items: Rc<Vec<TermValue>>,
+        pos: usize,
+    },
+    Generator(Rc<GeneratorRef>),
+}
+
+pub fn iterate(value: &TermValue) -> Result<LoxIterator, TermValue> {
+    match value {
+        TermValue::Range(range) => {
+            if range.step == 0.0 {
+                return Err(TermValue::Error("range step cannot be zero".to_string()));
+            }
+            Ok(LoxIterator::Range {
+                next: range.start,
+                range: range.clone(),
+            })
+        }
+        // strings iterate by character, not by byte
+        TermValue::StringValue(s) => Ok(LoxIterator::Chars {
+            chars: s.chars().collect(),
+            pos: 0,
+        }),
+        TermValue::List(items) => Ok(LoxIterator::List {
+            items: items.clone(),
+            pos: 0,
+        }),
+        TermValue::Generator(handle) => Ok(LoxIterator::Generator(handle.clone())),
+        _ => Err(TermValue::Error(format!("{:?} is not iterable", value))),
+    }
+}
+
+impl LoxIterator {
+    // an Error from a generator body is returned as an item
+    pub fn next_item(&mut self, interp: &mut InterpVisit) -> Option<TermValue> {
+        match self {
+            LoxIterator::Range { next, range } => {
+                let done = match (range.step > 0.0, range.inclusive) {
+                    (true, true) => *next > range.end,
+                    (true, false) => *next >= range.end,
+                    (false, true) => *next < range.end,
+                    (false, false) => *next <= range.end,
+                };
+                if done {
+                    return None;
+                }
+                let val = *next;
+                *next += range.step;
+                Some(TermValue::Number(val))
+            }
+            LoxIterator::Chars { chars, pos } => {
+                let c = chars.get(*pos)?;
+                *pos += 1;
+                Some(TermValue::StringValue(c.to_string()))
+            }
+            LoxIterator::List { items, pos } => {
+                let item = items.get(*pos)?.clone();
+                *pos += 1;
+                Some(item)
+            }
+            LoxIterator::Generator(handle) => interp.resume_generator(handle),
+        }
+    }
+}
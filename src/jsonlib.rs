@@ -0,0 +1,355 @@
+use std::rc::Rc;
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+use crate::natives::{bool_value, count_arg, native, string_arg, type_name, NativeFunction};
+
+/*
+
+    The JSON library, bound as globals.
+    JSON arrays become lists, numbers, strings, booleans and null become the
+    matching values, and the other way round for json_stringify().
+    Lox has no maps, so a JSON object is an error on both sides rather than
+    being turned into something that would not survive the trip back.
+    Parse errors give the line and column inside the JSON text.
+
+*/
+pub const JSON: &[NativeFunction] = &[
+    native("json_parse", 1, 1, json_parse),
+    native("json_stringify", 1, 2, json_stringify),
+];
+
+// arrays nested deeper than this are an error instead of a stack overflow
+const MAX_DEPTH: usize = 512;
+
+// the widest indent json_stringify accepts, as JSON.stringify in javascript
+const MAX_INDENT: usize = 10;
+
+// json_parse(text)
+fn json_parse(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let text = match string_arg("json_parse", args, 0) {
+        Ok(text) => text,
+        Err(err) => return err,
+    };
+    let mut parser = JsonParser { text, pos: 0 };
+    match parser.document() {
+        Ok(value) => value,
+        Err(msg) => {
+            let (line, column) = parser.location();
+            TermValue::Error(format!("json_parse: line {}:{} {}", line, column, msg))
+        }
+    }
+}
+
+struct JsonParser<'t> {
+    text: &'t str,
+    // byte offset of the next character
+    pos: usize,
+}
+
+impl<'t> JsonParser<'t> {
+    // the line and column of pos, both counted from 1 and in characters
+    fn location(&self) -> (usize, usize) {
+        let before = &self.text[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    // what was found at pos, for error messages
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(c) => format!("{:?}", c),
+            None => "the end of the text".to_string(),
+        }
+    }
+
+    fn expect(&mut self, word: &str, value: TermValue) -> Result<TermValue, String> {
+        if !self.text[self.pos..].starts_with(word) {
+            return Err(format!("expected {}, found {}", word, self.found()));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn document(&mut self) -> Result<TermValue, String> {
+        let value = self.value(0)?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => Err(format!("unexpected {} after the value", self.found())),
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<TermValue, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('[') => self.array(depth),
+            Some('"') => Ok(TermValue::StringValue(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.expect("true", bool_value(true)),
+            Some('f') => self.expect("false", bool_value(false)),
+            Some('n') => self.expect("null", TermValue::Nil),
+            Some('{') => Err("objects are not supported, lox has no maps".to_string()),
+            _ => Err(format!("expected a value, found {}", self.found())),
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<TermValue, String> {
+        if depth == MAX_DEPTH {
+            return Err(format!("arrays nested more than {} deep", MAX_DEPTH));
+        }
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(TermValue::List(Rc::new(items)));
+        }
+        loop {
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(TermValue::List(Rc::new(items)));
+                }
+                _ => return Err(format!("expected ',' or ']', found {}", self.found())),
+            }
+        }
+    }
+
+    // -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> Result<TermValue, String> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some('0') => self.pos += 1,
+            Some('1'..='9') => self.digits(),
+            _ => return Err(format!("expected a digit, found {}", self.found())),
+        }
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            self.required_digits()?;
+        }
+        if let Some('e' | 'E') = self.peek() {
+            self.pos += 1;
+            if let Some('+' | '-') = self.peek() {
+                self.pos += 1;
+            }
+            self.required_digits()?;
+        }
+        let literal = &self.text[start..self.pos];
+        match literal.parse::<f64>() {
+            Ok(x) if x.is_finite() => Ok(TermValue::Number(x)),
+            _ => {
+                self.pos = start;
+                Err(format!("number {} is too large", literal))
+            }
+        }
+    }
+
+    fn digits(&mut self) {
+        while let Some('0'..='9') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn required_digits(&mut self) -> Result<(), String> {
+        match self.peek() {
+            Some('0'..='9') => {
+                self.digits();
+                Ok(())
+            }
+            _ => Err(format!("expected a digit, found {}", self.found())),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let start = self.pos;
+            match self.bump() {
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.escape()?),
+                Some(c) if (c as u32) < 0x20 => {
+                    self.pos = start;
+                    return Err(format!("unescaped control character {:?} in a string", c));
+                }
+                Some(c) => s.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    // the character of the escape after a backslash
+    fn escape(&mut self) -> Result<char, String> {
+        let start = self.pos;
+        let c = match self.bump() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let unit = self.hex4()?;
+                // a high surrogate has to be followed by a low one
+                let code = if (0xd800..0xdc00).contains(&unit) {
+                    if !self.text[self.pos..].starts_with("\\u") {
+                        self.pos = start;
+                        return Err("unpaired surrogate in a \\u escape".to_string());
+                    }
+                    self.pos += 2;
+                    let low = self.hex4()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        self.pos = start;
+                        return Err("unpaired surrogate in a \\u escape".to_string());
+                    }
+                    0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    unit
+                };
+                match char::from_u32(code) {
+                    Some(c) => c,
+                    None => {
+                        self.pos = start;
+                        return Err("unpaired surrogate in a \\u escape".to_string());
+                    }
+                }
+            }
+            Some(c) => {
+                self.pos = start;
+                return Err(format!("invalid escape \\{}", c));
+            }
+            None => return Err("unterminated string".to_string()),
+        };
+        Ok(c)
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let hex = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| "expected 4 hex digits in a \\u escape".to_string())?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(hex, 16).unwrap())
+    }
+}
+
+// json_stringify(value, indent), on one line or with each element on its own
+// line indented by indent spaces per level, from 0 to 10
+fn json_stringify(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let indent = match args.get(1) {
+        Some(_) => match count_arg("json_stringify", args, 1) {
+            Ok(indent) if indent <= MAX_INDENT => indent,
+            Ok(indent) => {
+                return TermValue::Error(format!(
+                    "json_stringify: indent must be at most {}, got {}",
+                    MAX_INDENT, indent
+                ))
+            }
+            Err(err) => return err,
+        },
+        None => 0,
+    };
+    let mut text = String::new();
+    match stringify(&args[0], indent, 0, &mut Vec::new(), &mut text) {
+        Ok(()) => TermValue::StringValue(text),
+        Err(msg) => TermValue::Error(format!("json_stringify: {}", msg)),
+    }
+}
+
+// open holds the lists between the top level value and this one
+fn stringify(
+    val: &TermValue,
+    indent: usize,
+    level: usize,
+    open: &mut Vec<*const Vec<TermValue>>,
+    text: &mut String,
+) -> Result<(), String> {
+    match val {
+        TermValue::Number(x) if x.is_finite() => text.push_str(&format!("{}", x)),
+        TermValue::Number(x) => return Err(format!("{} cannot be written as JSON", x)),
+        TermValue::True => text.push_str("true"),
+        TermValue::False => text.push_str("false"),
+        TermValue::Nil | TermValue::Empty => text.push_str("null"),
+        TermValue::StringValue(s) => quote(s, text),
+        TermValue::List(items) => {
+            let ptr = Rc::as_ptr(items);
+            if open.contains(&ptr) {
+                return Err("cannot write a list that contains itself".to_string());
+            }
+            if items.is_empty() {
+                text.push_str("[]");
+                return Ok(());
+            }
+            open.push(ptr);
+            text.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    text.push(',');
+                }
+                newline(indent, level + 1, text);
+                stringify(item, indent, level + 1, open, text)?;
+            }
+            newline(indent, level, text);
+            text.push(']');
+            open.pop();
+        }
+        other => {
+            return Err(format!(
+                "{} values cannot be written as JSON",
+                type_name(other)
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn newline(indent: usize, level: usize, text: &mut String) {
+    if indent > 0 {
+        text.push('\n');
+        text.push_str(&" ".repeat(indent * level));
+    }
+}
+
+fn quote(s: &str, text: &mut String) {
+    text.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            '\u{8}' => text.push_str("\\b"),
+            '\u{c}' => text.push_str("\\f"),
+            c if (c as u32) < 0x20 => text.push_str(&format!("\\u{:04x}", c as u32)),
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+}
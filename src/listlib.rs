@@ -0,0 +1,306 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+use crate::iterator;
+use crate::natives::{bool_value, native, number_arg, type_name, NativeFunction};
+
+/*
+
+    The collection library, bound as globals.
+    Every function takes anything a for-in loop can walk, a list, a range, a
+    string or a generator, and gives back a new list.
+    The function arguments can be lox functions, which give back the value of
+    their return statement, natives or enum constructors. They are called
+    through InterpVisit::call_value and an error they return stops the whole call.
+    As in an if or a while, only true counts as true for filter, any and all.
+
+*/
+pub const LIST: &[NativeFunction] = &[
+    native("map", 2, 2, map),
+    native("filter", 2, 2, filter),
+    native("reduce", 2, 3, reduce),
+    native("sort", 1, 2, sort),
+    native("reverse", 1, 1, reverse),
+    native("zip", 1, usize::MAX, zip),
+    native("enumerate", 1, 2, enumerate),
+    native("any", 1, 2, any),
+    native("all", 1, 2, all),
+    native("sum", 1, 1, sum),
+];
+
+fn list(items: Vec<TermValue>) -> TermValue {
+    TermValue::List(Rc::new(items))
+}
+
+// the items of argument i, the values a for-in loop over it would see
+fn items_arg(
+    interp: &mut InterpVisit,
+    name: &str,
+    args: &[TermValue],
+    i: usize,
+) -> Result<Vec<TermValue>, TermValue> {
+    if let TermValue::List(items) = &args[i] {
+        return Ok(items.to_vec());
+    }
+    let Ok(mut iter) = iterator::iterate(&args[i]) else {
+        return Err(TermValue::Error(format!(
+            "{} expects a list, range, string or generator as argument {}, got {}",
+            name,
+            i + 1,
+            type_name(&args[i])
+        )));
+    };
+    let mut items = Vec::new();
+    while let Some(item) = iter.next_item(interp) {
+        if let TermValue::Error(_) = item {
+            return Err(item);
+        }
+        items.push(item);
+    }
+    Ok(items)
+}
+
+// argument i, which has to be something that can be called
+fn function_arg<'v>(
+    name: &str,
+    args: &'v [TermValue],
+    i: usize,
+) -> Result<&'v TermValue, TermValue> {
+    match &args[i] {
+        f @ (TermValue::Function(_) | TermValue::Native(_) | TermValue::Constructor(..)) => Ok(f),
+        arg => Err(TermValue::Error(format!(
+            "{} expects a function as argument {}, got {}",
+            name,
+            i + 1,
+            type_name(arg)
+        ))),
+    }
+}
+
+// calls f, turning an error it returns into Err
+fn call(
+    interp: &mut InterpVisit,
+    f: &TermValue,
+    args: Vec<TermValue>,
+) -> Result<TermValue, TermValue> {
+    match interp.call_value(f, args, Vec::new()) {
+        TermValue::Error(msg) => Err(TermValue::Error(msg)),
+        result => Ok(result),
+    }
+}
+
+// map(items, f), f of every item
+fn map(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let mapped = items_arg(interp, "map", args, 0).and_then(|items| {
+        let f = function_arg("map", args, 1)?;
+        items
+            .into_iter()
+            .map(|item| call(interp, f, vec![item]))
+            .collect()
+    });
+    mapped.map_or_else(|err| err, list)
+}
+
+// filter(items, f), the items for which f is true
+fn filter(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let filtered = items_arg(interp, "filter", args, 0).and_then(|items| {
+        let f = function_arg("filter", args, 1)?;
+        let mut kept = Vec::new();
+        for item in items {
+            if let TermValue::True = call(interp, f, vec![item.clone()])? {
+                kept.push(item);
+            }
+        }
+        Ok(kept)
+    });
+    filtered.map_or_else(|err| err, list)
+}
+
+// reduce(items, f, initial) folds the items into one value with f(acc, item),
+// without an initial value the first item is the start and items cannot be empty
+fn reduce(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let reduced = items_arg(interp, "reduce", args, 0).and_then(|items| {
+        let f = function_arg("reduce", args, 1)?;
+        let mut items = items.into_iter();
+        let mut acc = match args.get(2) {
+            Some(initial) => initial.clone(),
+            None => items.next().ok_or_else(|| {
+                TermValue::Error("reduce of an empty list with no initial value".to_string())
+            })?,
+        };
+        for item in items {
+            acc = call(interp, f, vec![acc, item])?;
+        }
+        Ok(acc)
+    });
+    reduced.unwrap_or_else(|err| err)
+}
+
+// sort(items, compare), the items in ascending order.
+// Without compare the items have to be all numbers or all strings, compare(a, b)
+// returns a number below zero when a comes first, above zero when b comes first
+// and zero when either order will do. The sort is stable.
+fn sort(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let sorted = items_arg(interp, "sort", args, 0).and_then(|items| {
+        let compare = args
+            .get(1)
+            .map(|_| function_arg("sort", args, 1))
+            .transpose()?;
+        merge_sort(items, &mut |a, b| match compare {
+            Some(f) => match call(interp, f, vec![a.clone(), b.clone()])? {
+                TermValue::Number(x) if !x.is_nan() => Ok(x.partial_cmp(&0.0).unwrap()),
+                other => Err(TermValue::Error(format!(
+                    "sort comparator has to return a number, got {}",
+                    type_name(&other)
+                ))),
+            },
+            None => natural_order(a, b),
+        })
+    });
+    sorted.map_or_else(|err| err, list)
+}
+
+fn natural_order(a: &TermValue, b: &TermValue) -> Result<Ordering, TermValue> {
+    match (a, b) {
+        (TermValue::Number(x), TermValue::Number(y)) => x.partial_cmp(y).ok_or_else(|| {
+            TermValue::Error("sort cannot order NaN without a comparator".to_string())
+        }),
+        (TermValue::StringValue(x), TermValue::StringValue(y)) => Ok(x.cmp(y)),
+        _ => Err(TermValue::Error(format!(
+            "sort cannot order a {} and a {} without a comparator",
+            type_name(a),
+            type_name(b)
+        ))),
+    }
+}
+
+// a stable merge sort that stops at the first error of compare, the slice sorts
+// of the standard library may panic when a comparator is not a total order
+fn merge_sort(
+    items: Vec<TermValue>,
+    compare: &mut dyn FnMut(&TermValue, &TermValue) -> Result<Ordering, TermValue>,
+) -> Result<Vec<TermValue>, TermValue> {
+    if items.len() < 2 {
+        return Ok(items);
+    }
+    let mut left = items;
+    let right = left.split_off(left.len() / 2);
+    let mut left = merge_sort(left, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        if compare(a, b)? == Ordering::Greater {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+// reverse(items), the items last to first
+fn reverse(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match items_arg(interp, "reverse", args, 0) {
+        Ok(mut items) => {
+            items.reverse();
+            list(items)
+        }
+        Err(err) => err,
+    }
+}
+
+// zip(a, b, ...), a list of [a[i], b[i], ...] as long as the shortest argument
+fn zip(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let mut columns = Vec::new();
+    for i in 0..args.len() {
+        match items_arg(interp, "zip", args, i) {
+            Ok(items) => columns.push(items.into_iter()),
+            Err(err) => return err,
+        }
+    }
+    let mut rows = Vec::new();
+    loop {
+        let row: Option<Vec<TermValue>> = columns.iter_mut().map(Iterator::next).collect();
+        match row {
+            Some(row) => rows.push(list(row)),
+            None => return list(rows),
+        }
+    }
+}
+
+// enumerate(items, start), a list of [index, item] with indices counting from
+// start, which defaults to 0
+fn enumerate(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let items = match items_arg(interp, "enumerate", args, 0) {
+        Ok(items) => items,
+        Err(err) => return err,
+    };
+    let start = match args.get(1) {
+        Some(_) => match number_arg("enumerate", args, 1) {
+            Ok(start) => start,
+            Err(err) => return err,
+        },
+        None => 0.0,
+    };
+    let pairs = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| list(vec![TermValue::Number(start + i as f64), item]))
+        .collect();
+    list(pairs)
+}
+
+// whether f is true for any (or all) of the items, or the items themselves
+// are true when there is no f. Stops at the first item that decides it.
+fn any_or_all(interp: &mut InterpVisit, name: &str, args: &[TermValue], any: bool) -> TermValue {
+    let decided = items_arg(interp, name, args, 0).and_then(|items| {
+        let f = args
+            .get(1)
+            .map(|_| function_arg(name, args, 1))
+            .transpose()?;
+        for item in items {
+            let test = match f {
+                Some(f) => call(interp, f, vec![item])?,
+                None => item,
+            };
+            if matches!(test, TermValue::True) == any {
+                return Ok(any);
+            }
+        }
+        Ok(!any)
+    });
+    decided.map_or_else(|err| err, bool_value)
+}
+
+fn any(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    any_or_all(interp, "any", args, true)
+}
+
+fn all(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    any_or_all(interp, "all", args, false)
+}
+
+// sum(items), the total of a list of numbers, 0 when it is empty
+fn sum(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match items_arg(interp, "sum", args, 0) {
+        Ok(items) => {
+            let mut total = 0.0;
+            for item in items {
+                match item {
+                    TermValue::Number(x) => total += x,
+                    other => {
+                        return TermValue::Error(format!(
+                            "sum expects a list of numbers, got a {} in it",
+                            type_name(&other)
+                        ))
+                    }
+                }
+            }
+            TermValue::Number(total)
+        }
+        Err(err) => err,
+    }
+}
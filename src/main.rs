@@ -1,16 +1,23 @@
+use std::fs;
 use std::path::PathBuf;
-use std::{env, fs};
+use std::process;
 
 use antlr_rust::tree::Visitable;
 use antlr_rust::Parser;
 
 use antlr_rust::{common_token_stream::CommonTokenStream, InputStream};
+use clap::{value_parser, Arg, ArgAction, Command};
 use trace::initlog;
 
+use crate::docvisitor::{doc_comments, DocVisit};
 use crate::errorvisitor::{ErrDetectVisit, ErrVal, MyErrorListener};
 use crate::{
     antlr::{loxlexer::LoxLexer, loxparser::LoxParser},
-    interpvisitor::InterpVisit,
+    interpvisitor::{InterpVisit, TermValue},
+    randlib::Rng,
+    resolvevisitor::ResolveVisit,
+    timelib::FakeClock,
+    typecheckvisitor::TypeCheckVisit,
 };
 mod antlr {
     pub mod loxlexer;
@@ -18,15 +25,120 @@ mod antlr {
     pub mod loxparser;
     pub mod loxvisitor;
 }
+mod display;
+mod docvisitor;
 mod errorvisitor;
+mod generator;
 mod interpvisitor;
+mod iolib;
+mod iterator;
+mod jsonlib;
+mod listlib;
+mod mathlib;
+mod natives;
+mod number;
+mod randlib;
+mod resolvevisitor;
+mod stringlib;
+mod syslib;
+mod timelib;
 mod trace;
+mod typecheckvisitor;
+
+fn cli() -> Command {
+    Command::new("mylox")
+        .about("Lox interpreter")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .arg(Arg::new("script").required(true).help("Script to run"))
+        .arg(
+            Arg::new("args")
+                .num_args(0..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .help("Arguments passed to the script as the args list"),
+        )
+        .arg(
+            Arg::new("warn-exhaustive")
+                .long("warn-exhaustive")
+                .action(ArgAction::SetTrue)
+                .help("Warn when a match on a boolean or an enum value does not cover every value"),
+        )
+        .arg(
+            Arg::new("print-result")
+                .long("print-result")
+                .action(ArgAction::SetTrue)
+                .help("Print the value of the program as a Result: line after running it"),
+        )
+        .arg(
+            Arg::new("fake-clock")
+                .long("fake-clock")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(f64))
+                .help("Start time() at SECONDS since the epoch and only advance the clock on sleep()"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("N")
+                .value_parser(value_parser!(u64))
+                .help("Seed the random number generator so a run can be repeated exactly"),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Report errors in a script without running it")
+                .arg(
+                    Arg::new("types")
+                        .long("types")
+                        .action(ArgAction::SetTrue)
+                        .help("Also check type annotations"),
+                )
+                .arg(
+                    Arg::new("docs")
+                        .long("docs")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the /// doc comments of declarations"),
+                )
+                .arg(Arg::new("script").required(true).help("Script to check")),
+        )
+}
+
+// exit statuses, from the BSD sysexits.h convention that clox follows too
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
 
 fn main() {
     initlog();
-    let args: Vec<String> = env::args().collect();
-    let contents = fs::read_to_string(PathBuf::from(args[1].clone()))
-        .expect("Should have been able to read the file");
+    process::exit(run());
+}
+
+// runs or checks the script and returns the exit status
+fn run() -> i32 {
+    let matches = match cli().try_get_matches() {
+        Ok(matches) => matches,
+        // --help and --version are not errors
+        Err(err) => {
+            let _ = err.print();
+            return if err.use_stderr() { EX_USAGE } else { 0 };
+        }
+    };
+    // check is Some((check types, print docs)) when only static checks are wanted
+    let (script, check) = match matches.subcommand() {
+        Some(("check", sub)) => (
+            sub.get_one::<String>("script").unwrap(),
+            Some((sub.get_flag("types"), sub.get_flag("docs"))),
+        ),
+        _ => (matches.get_one::<String>("script").unwrap(), None),
+    };
+    let contents = match fs::read_to_string(PathBuf::from(script)) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("Error: cannot read {}: {}", script, err);
+            return EX_NOINPUT;
+        }
+    };
 
     let mut lexer = LoxLexer::new(InputStream::new(contents.as_str()));
     lexer.remove_error_listeners();
@@ -42,17 +154,72 @@ fn main() {
         Ok(root) => {
             let mut ed_visitor = ErrDetectVisit::new();
             root.accept(&mut ed_visitor);
-            if ed_visitor.val != ErrVal::Empty {
-                println!("Error: {:?}", ed_visitor.val);
-                return;
+            // the listener has already reported the error
+            if ed_visitor.val == ErrVal::Error {
+                return EX_DATAERR;
+            }
+
+            let mut resolver = ResolveVisit::new();
+            root.accept(&mut resolver);
+            if !resolver.errors.is_empty() {
+                for err in resolver.errors.iter() {
+                    eprintln!("Error: {}", err);
+                }
+                return EX_DATAERR;
+            }
+
+            if let Some((types, docs)) = check {
+                if docs {
+                    let mut doc_visitor = DocVisit::new(doc_comments(parser.get_input_stream()));
+                    root.accept(&mut doc_visitor);
+                    for doc in doc_visitor.docs.iter() {
+                        println!("{} (line {}):", doc.name, doc.line);
+                        for line in doc.text.lines() {
+                            println!("    {}", line);
+                        }
+                    }
+                }
+                if types {
+                    let mut checker = TypeCheckVisit::new();
+                    root.accept(&mut checker);
+                    for err in checker.errors.iter() {
+                        eprintln!("Error: {}", err);
+                    }
+                    if !checker.errors.is_empty() {
+                        return EX_DATAERR;
+                    }
+                }
+                return 0;
             }
 
             let mut visitor = InterpVisit::new();
+            visitor.locals = resolver.locals;
+            visitor.warn_non_exhaustive = matches.get_flag("warn-exhaustive");
+            if let Some(start) = matches.get_one::<f64>("fake-clock") {
+                visitor.clock = Box::new(FakeClock::new(*start));
+            }
+            if let Some(seed) = matches.get_one::<u64>("seed") {
+                visitor.rng = Rng::new(*seed);
+            }
+            if let Some(args) = matches.get_many::<String>("args") {
+                visitor.set_args(args.cloned().collect());
+            }
             root.accept(&mut visitor);
-            println!("Result: {:?}", visitor.value());
+            if let Some(code) = visitor.exit_code {
+                return code;
+            }
+            if let TermValue::Error(msg) = visitor.value() {
+                eprintln!("Error: {}", msg);
+                return EX_SOFTWARE;
+            }
+            if matches.get_flag("print-result") {
+                println!("Result: {:?}", visitor.value());
+            }
+            0
         }
         Err(e) => {
-            println!("Error: {:?}", e);
+            eprintln!("Error: {:?}", e);
+            EX_DATAERR
         }
     }
 }
@@ -0,0 +1,213 @@
+use std::f64::consts;
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+use crate::natives::{bool_value, native, number_arg, NativeFunction};
+
+/*
+
+    The math library, bound as globals.
+    Arguments that are not numbers and arguments outside a function's domain,
+    like sqrt(-1), are runtime errors rather than a silent NaN.
+
+*/
+pub const CONSTANTS: &[(&str, f64)] = &[("pi", consts::PI), ("e", consts::E)];
+
+pub const MATH: &[NativeFunction] = &[
+    native("sqrt", 1, 1, sqrt),
+    native("pow", 2, 2, pow),
+    native("floor", 1, 1, floor),
+    native("ceil", 1, 1, ceil),
+    native("round", 1, 1, round),
+    native("abs", 1, 1, abs),
+    native("min", 1, usize::MAX, min),
+    native("max", 1, usize::MAX, max),
+    native("sin", 1, 1, sin),
+    native("cos", 1, 1, cos),
+    native("tan", 1, 1, tan),
+    native("asin", 1, 1, asin),
+    native("acos", 1, 1, acos),
+    native("atan", 1, 1, atan),
+    native("atan2", 2, 2, atan2),
+    native("exp", 1, 1, exp),
+    native("log", 1, 2, log),
+    native("log10", 1, 1, log10),
+    native("log2", 1, 1, log2),
+    native("isnan", 1, 1, isnan),
+    native("isinf", 1, 1, isinf),
+];
+
+// applies f to the single number argument
+fn unary(name: &str, args: &[TermValue], f: fn(f64) -> f64) -> TermValue {
+    match number_arg(name, args, 0) {
+        Ok(x) => TermValue::Number(f(x)),
+        Err(err) => err,
+    }
+}
+
+// as unary, but the argument has to be accepted by in_domain
+fn unary_in(
+    name: &str,
+    args: &[TermValue],
+    f: fn(f64) -> f64,
+    in_domain: fn(f64) -> bool,
+    domain: &str,
+) -> TermValue {
+    match number_arg(name, args, 0) {
+        Ok(x) if in_domain(x) || x.is_nan() => TermValue::Number(f(x)),
+        Ok(x) => TermValue::Error(format!("{} expects {}, got {}", name, domain, x)),
+        Err(err) => err,
+    }
+}
+
+fn sqrt(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary_in("sqrt", args, f64::sqrt, |x| x >= 0.0, "a number >= 0")
+}
+
+// pow(x, y), a negative x needs a whole y and 0 cannot be raised to a negative y
+fn pow(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match (number_arg("pow", args, 0), number_arg("pow", args, 1)) {
+        (Ok(x), Ok(y)) if x < 0.0 && y.fract() != 0.0 => TermValue::Error(format!(
+            "pow expects a whole exponent for a negative base, got {}",
+            y
+        )),
+        (Ok(x), Ok(y)) if x == 0.0 && y < 0.0 => TermValue::Error(format!(
+            "pow expects an exponent >= 0 for a base of 0, got {}",
+            y
+        )),
+        (Ok(x), Ok(y)) => TermValue::Number(x.powf(y)),
+        (Err(err), _) | (_, Err(err)) => err,
+    }
+}
+
+fn floor(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("floor", args, f64::floor)
+}
+
+fn ceil(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("ceil", args, f64::ceil)
+}
+
+// halves round away from zero
+fn round(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("round", args, f64::round)
+}
+
+fn abs(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("abs", args, f64::abs)
+}
+
+// min and max take any number of arguments, or a single list of numbers
+fn numbers(name: &str, args: &[TermValue]) -> Result<Vec<f64>, TermValue> {
+    if let [TermValue::List(items)] = args {
+        if items.is_empty() {
+            return Err(TermValue::Error(format!("{} of an empty list", name)));
+        }
+        return (0..items.len())
+            .map(|i| number_arg(name, items, i))
+            .collect();
+    }
+    (0..args.len()).map(|i| number_arg(name, args, i)).collect()
+}
+
+fn min(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match numbers("min", args) {
+        Ok(nums) => TermValue::Number(nums.into_iter().fold(f64::INFINITY, f64::min)),
+        Err(err) => err,
+    }
+}
+
+fn max(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match numbers("max", args) {
+        Ok(nums) => TermValue::Number(nums.into_iter().fold(f64::NEG_INFINITY, f64::max)),
+        Err(err) => err,
+    }
+}
+
+fn sin(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("sin", args, f64::sin)
+}
+
+fn cos(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("cos", args, f64::cos)
+}
+
+fn tan(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("tan", args, f64::tan)
+}
+
+fn asin(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary_in(
+        "asin",
+        args,
+        f64::asin,
+        |x| (-1.0..=1.0).contains(&x),
+        "a number from -1 to 1",
+    )
+}
+
+fn acos(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary_in(
+        "acos",
+        args,
+        f64::acos,
+        |x| (-1.0..=1.0).contains(&x),
+        "a number from -1 to 1",
+    )
+}
+
+fn atan(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("atan", args, f64::atan)
+}
+
+// atan2(y, x)
+fn atan2(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match (number_arg("atan2", args, 0), number_arg("atan2", args, 1)) {
+        (Ok(y), Ok(x)) => TermValue::Number(y.atan2(x)),
+        (Err(err), _) | (_, Err(err)) => err,
+    }
+}
+
+fn exp(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary("exp", args, f64::exp)
+}
+
+// log(x) is the natural logarithm, log(x, base) the logarithm to base
+fn log(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    if args.len() == 1 {
+        return unary_in("log", args, f64::ln, |x| x > 0.0, "a number > 0");
+    }
+    match (number_arg("log", args, 0), number_arg("log", args, 1)) {
+        (Ok(x), Ok(base)) if (x > 0.0 || x.is_nan()) && base > 0.0 && base != 1.0 => {
+            TermValue::Number(x.log(base))
+        }
+        (Ok(x), Ok(_)) if x <= 0.0 => {
+            TermValue::Error(format!("log expects a number > 0, got {}", x))
+        }
+        (Ok(_), Ok(base)) => {
+            TermValue::Error(format!("log expects a base > 0 other than 1, got {}", base))
+        }
+        (Err(err), _) | (_, Err(err)) => err,
+    }
+}
+
+fn log10(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary_in("log10", args, f64::log10, |x| x > 0.0, "a number > 0")
+}
+
+fn log2(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    unary_in("log2", args, f64::log2, |x| x > 0.0, "a number > 0")
+}
+
+fn isnan(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match number_arg("isnan", args, 0) {
+        Ok(x) => bool_value(x.is_nan()),
+        Err(err) => err,
+    }
+}
+
+fn isinf(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match number_arg("isinf", args, 0) {
+        Ok(x) => bool_value(x.is_infinite()),
+        Err(err) => err,
+    }
+}
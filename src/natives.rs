@@ -0,0 +1,159 @@
+use crate::interpvisitor::{InterpVisit, RangeValue, TermValue};
+use crate::iolib;
+use crate::jsonlib;
+use crate::listlib;
+use crate::mathlib;
+use crate::randlib;
+use crate::stringlib;
+use crate::syslib;
+use crate::timelib;
+
+/*
+
+    Native functions are implemented in rust and bound as globals when the
+    interpreter starts. The interpreter checks the argument count against
+    min_args / max_args before calling, so the functions only need to check types.
+
+*/
+pub type NativeFn = fn(&mut InterpVisit, &[TermValue]) -> TermValue;
+
+#[derive(Debug, Clone, Copy)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub min_args: usize,
+    pub max_args: usize,
+    pub func: NativeFn,
+}
+
+pub const fn native(
+    name: &'static str,
+    min_args: usize,
+    max_args: usize,
+    func: NativeFn,
+) -> NativeFunction {
+    NativeFunction {
+        name,
+        min_args,
+        max_args,
+        func,
+    }
+}
+
+pub const CORE: &[NativeFunction] = &[native("range", 1, 3, range), native("str", 1, 1, str)];
+
+// every native function that is bound as a global
+pub fn globals() -> impl Iterator<Item = &'static NativeFunction> {
+    CORE.iter()
+        .chain(mathlib::MATH.iter())
+        .chain(stringlib::STRING.iter())
+        .chain(listlib::LIST.iter())
+        .chain(iolib::IO.iter())
+        .chain(timelib::TIME.iter())
+        .chain(randlib::RANDOM.iter())
+        .chain(jsonlib::JSON.iter())
+        .chain(syslib::SYS.iter())
+}
+
+// numbers bound as globals next to the native functions
+pub fn constants() -> impl Iterator<Item = &'static (&'static str, f64)> {
+    mathlib::CONSTANTS.iter()
+}
+
+// the name of the type of a value, for error messages
+pub fn type_name(val: &TermValue) -> &'static str {
+    match val {
+        TermValue::Number(_) => "number",
+        TermValue::True | TermValue::False => "bool",
+        TermValue::Nil | TermValue::Empty => "nil",
+        TermValue::StringValue(_) => "string",
+        TermValue::Error(_) => "error",
+        TermValue::Function(_) | TermValue::Native(_) | TermValue::Constructor(..) => "fun",
+        TermValue::Range(_) => "range",
+        TermValue::List(_) => "list",
+        TermValue::Generator(_) => "generator",
+        TermValue::Enum(_) => "enum",
+    }
+}
+
+pub fn bool_value(b: bool) -> TermValue {
+    if b {
+        TermValue::True
+    } else {
+        TermValue::False
+    }
+}
+
+// argument i of the native name, which has to be a number
+pub fn number_arg(name: &str, args: &[TermValue], i: usize) -> Result<f64, TermValue> {
+    match &args[i] {
+        TermValue::Number(x) => Ok(*x),
+        arg => Err(TermValue::Error(format!(
+            "{} expects a number as argument {}, got {}",
+            name,
+            i + 1,
+            type_name(arg)
+        ))),
+    }
+}
+
+// range(end), range(start, end) or range(start, end, step)
+fn range(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let mut nums = Vec::new();
+    for arg in args {
+        match arg {
+            TermValue::Number(x) => nums.push(*x),
+            _ => return TermValue::Error("range arguments must be numbers".to_string()),
+        }
+    }
+    let (start, end, step) = match nums[..] {
+        [end] => (0.0, end, 1.0),
+        [start, end] => (start, end, 1.0),
+        [start, end, step] => (start, end, step),
+        _ => unreachable!(),
+    };
+    if step == 0.0 {
+        return TermValue::Error("range step cannot be zero".to_string());
+    }
+    TermValue::Range(RangeValue {
+        start,
+        end,
+        step,
+        inclusive: false,
+    })
+}
+
+// str(x), the text print would show for x
+fn str(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    TermValue::StringValue(interp.display(&args[0]))
+}
+
+// argument i of the native name, which has to be a string
+pub fn string_arg<'v>(name: &str, args: &'v [TermValue], i: usize) -> Result<&'v str, TermValue> {
+    match &args[i] {
+        TermValue::StringValue(s) => Ok(s),
+        arg => Err(TermValue::Error(format!(
+            "{} expects a string as argument {}, got {}",
+            name,
+            i + 1,
+            type_name(arg)
+        ))),
+    }
+}
+
+// the largest count a native accepts, anything above is a mistake rather than a size
+pub const MAX_COUNT: f64 = u32::MAX as f64;
+
+// argument i of the native name, which has to be a whole number from 0 to MAX_COUNT
+pub fn count_arg(name: &str, args: &[TermValue], i: usize) -> Result<usize, TermValue> {
+    let x = number_arg(name, args, i)?;
+    if !(0.0..=MAX_COUNT).contains(&x) || x.fract() != 0.0 {
+        return Err(TermValue::Error(format!(
+            "{} expects a whole number from 0 to {} as argument {}, got {}",
+            name,
+            MAX_COUNT,
+            i + 1,
+            x
+        )));
+    }
+    Ok(x as usize)
+}
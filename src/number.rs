@@ -0,0 +1,152 @@
+use std::num::IntErrorKind;
+
+/*
+
+    Number literals are checked and converted here.
+    The lexer lets a NUMBER run on over any letters, digits and underscores, so a
+    malformed literal like 0x1G or 1__0 arrives as one token and can be reported
+    with its location instead of being split into a number and an identifier.
+    An _ separator may only appear between two digits.
+
+*/
+
+// the largest integer a number holds exactly
+const MAX_EXACT: u64 = 1 << 53;
+
+pub fn parse_number(text: &str) -> Result<f64, String> {
+    let (radix, digits) = match text.get(..2).map(|p| p.to_ascii_lowercase()).as_deref() {
+        Some("0x") => (16, &text[2..]),
+        Some("0b") => (2, &text[2..]),
+        Some("0o") => (8, &text[2..]),
+        _ => return parse_decimal(text),
+    };
+    let digits = strip_separators(digits, radix).ok_or_else(|| malformed(text))?;
+    match u64::from_str_radix(&digits, radix) {
+        Ok(value) if value <= MAX_EXACT => Ok(value as f64),
+        Ok(_) => Err(too_large(text)),
+        Err(e) if *e.kind() == IntErrorKind::PosOverflow => Err(too_large(text)),
+        Err(_) => Err(malformed(text)),
+    }
+}
+
+fn parse_decimal(text: &str) -> Result<f64, String> {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None),
+    };
+    let mut clean = strip_separators(int, 10).ok_or_else(|| malformed(text))?;
+    if let Some(frac) = frac {
+        clean.push('.');
+        clean.push_str(&strip_separators(frac, 10).ok_or_else(|| malformed(text))?);
+    }
+    if let Some(exponent) = exponent {
+        let digits = exponent.trim_start_matches(['+', '-']);
+        // at most one sign
+        if exponent.len() - digits.len() > 1 {
+            return Err(malformed(text));
+        }
+        clean.push('e');
+        clean.push_str(&exponent[..exponent.len() - digits.len()]);
+        clean.push_str(&strip_separators(digits, 10).ok_or_else(|| malformed(text))?);
+    }
+    match clean.parse::<f64>() {
+        Ok(value) if value.is_infinite() => Err(too_large(text)),
+        Ok(value) => Ok(value),
+        Err(_) => Err(malformed(text)),
+    }
+}
+
+// the digits without their separators, None if they are not all digits of the radix
+// or a separator is not between two digits
+fn strip_separators(digits: &str, radix: u32) -> Option<String> {
+    if digits.is_empty() || digits.starts_with('_') || digits.ends_with('_') {
+        return None;
+    }
+    if digits.contains("__") {
+        return None;
+    }
+    if !digits.chars().all(|c| c == '_' || c.is_digit(radix)) {
+        return None;
+    }
+    Some(digits.replace('_', ""))
+}
+
+fn malformed(text: &str) -> String {
+    format!("malformed number literal {}", text)
+}
+
+fn too_large(text: &str) -> String {
+    format!("number literal {} is too large", text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_number;
+
+    #[test]
+    fn separators() {
+        assert_eq!(parse_number("1_000"), Ok(1000.0));
+        assert_eq!(parse_number("1_0.2_5"), Ok(10.25));
+        assert_eq!(parse_number("0xF_F"), Ok(255.0));
+        for text in ["1__0", "_1", "1_", "1_.5", "1._5", "0x_1", "1e_5"] {
+            assert_eq!(
+                parse_number(text),
+                Err(format!("malformed number literal {}", text))
+            );
+        }
+    }
+
+    #[test]
+    fn radixes() {
+        assert_eq!(parse_number("0x1f"), Ok(31.0));
+        assert_eq!(parse_number("0XFF"), Ok(255.0));
+        assert_eq!(parse_number("0b1010"), Ok(10.0));
+        assert_eq!(parse_number("0B11"), Ok(3.0));
+        assert_eq!(parse_number("0o17"), Ok(15.0));
+        assert_eq!(parse_number("0O7"), Ok(7.0));
+        assert_eq!(parse_number("42"), Ok(42.0));
+        for text in ["0x1G", "0b102", "0o8", "0x", "12ab"] {
+            assert_eq!(
+                parse_number(text),
+                Err(format!("malformed number literal {}", text))
+            );
+        }
+    }
+
+    #[test]
+    fn exponents() {
+        assert_eq!(parse_number("1e3"), Ok(1000.0));
+        assert_eq!(parse_number("1E3"), Ok(1000.0));
+        assert_eq!(parse_number("1.5e+2"), Ok(150.0));
+        assert_eq!(parse_number("25e-2"), Ok(0.25));
+        for text in ["1e", "1e+", "1e+-2", "1e--2", "1e2.5"] {
+            assert_eq!(
+                parse_number(text),
+                Err(format!("malformed number literal {}", text))
+            );
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        // 2^53 is the last integer a number holds exactly
+        assert_eq!(parse_number("0x20000000000000"), Ok(9007199254740992.0));
+        for text in [
+            "0x20000000000001",
+            "0xFFFFFFFFFFFFFFFFFF",
+            "1e400",
+            "1_0e3_09",
+        ] {
+            assert_eq!(
+                parse_number(text),
+                Err(format!("number literal {} is too large", text))
+            );
+        }
+        // a decimal integer past 2^53 rounds like any other decimal
+        assert_eq!(parse_number("9007199254740993"), Ok(9007199254740992.0));
+    }
+}
@@ -0,0 +1,149 @@
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+use crate::natives::{native, number_arg, type_name, NativeFunction};
+
+/*
+
+    The random number library, bound as globals.
+    Numbers come from a splitmix64 generator held by the interpreter. It starts
+    from the time the interpreter was created unless a seed is given, with
+    seed(n) or the --seed option, after which a run draws the same numbers
+    every time.
+    Lists cannot be changed in place, so shuffle() gives a new list.
+
+*/
+pub const RANDOM: &[NativeFunction] = &[
+    native("random", 0, 0, random),
+    native("random_int", 2, 2, random_int),
+    native("shuffle", 1, 1, shuffle),
+    native("choice", 1, 1, choice),
+    native("seed", 1, 1, seed),
+];
+
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // a number from 0 up to but not including 1
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // a number from 0 up to but not including n, without the bias of a plain %
+    pub fn below(&mut self, n: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        Self::new(nanos)
+    }
+}
+
+// argument i, which has to be a whole number
+fn int_arg(name: &str, args: &[TermValue], i: usize) -> Result<i64, TermValue> {
+    let x = number_arg(name, args, i)?;
+    if x.fract() != 0.0 || x.abs() > (1u64 << 53) as f64 {
+        return Err(TermValue::Error(format!(
+            "{} expects a whole number as argument {}, got {}",
+            name,
+            i + 1,
+            x
+        )));
+    }
+    Ok(x as i64)
+}
+
+// argument 0, which has to be a list
+fn list_arg<'v>(name: &str, args: &'v [TermValue]) -> Result<&'v Rc<Vec<TermValue>>, TermValue> {
+    match &args[0] {
+        TermValue::List(items) => Ok(items),
+        arg => Err(TermValue::Error(format!(
+            "{} expects a list, got {}",
+            name,
+            type_name(arg)
+        ))),
+    }
+}
+
+// random(), a number from 0 up to but not including 1
+fn random(interp: &mut InterpVisit, _args: &[TermValue]) -> TermValue {
+    TermValue::Number(interp.rng.next_f64())
+}
+
+// random_int(a, b), a whole number from a to b, both included
+fn random_int(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let (low, high) = match (
+        int_arg("random_int", args, 0),
+        int_arg("random_int", args, 1),
+    ) {
+        (Ok(low), Ok(high)) => (low, high),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    if low > high {
+        return TermValue::Error(format!(
+            "random_int expects a <= b, got {} and {}",
+            low, high
+        ));
+    }
+    let offset = interp.rng.below((high - low) as u64 + 1);
+    TermValue::Number((low + offset as i64) as f64)
+}
+
+// shuffle(list), a copy of list in random order
+fn shuffle(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let mut items = match list_arg("shuffle", args) {
+        Ok(items) => items.to_vec(),
+        Err(err) => return err,
+    };
+    for i in (1..items.len()).rev() {
+        let j = interp.rng.below(i as u64 + 1) as usize;
+        items.swap(i, j);
+    }
+    TermValue::List(Rc::new(items))
+}
+
+// choice(list), a random element of a list that is not empty
+fn choice(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match list_arg("choice", args) {
+        Ok(items) if items.is_empty() => TermValue::Error("choice of an empty list".to_string()),
+        Ok(items) => items[interp.rng.below(items.len() as u64) as usize].clone(),
+        Err(err) => err,
+    }
+}
+
+// seed(n) restarts the generator, the same n gives the same numbers
+fn seed(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match int_arg("seed", args, 0) {
+        Ok(n) => {
+            interp.rng = Rng::new(n as u64);
+            TermValue::Nil
+        }
+        Err(err) => err,
+    }
+}
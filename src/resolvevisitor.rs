@@ -0,0 +1,391 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::antlr::loxparser::{
+    Assignment_altContext, BlockContext, BreakStmtContext, ContinueStmtContext,
+    DeclarationContextAll, Destructure_altContext, EnumDeclContext, EnumDeclContextAttrs,
+    EnumVariantContextAttrs, EnumerationContextAttrs, ForInStmtContext, ForInStmtContextAttrs,
+    ForStmtContext, FunctionContextAttrs, FunctionDeclContext, FunctionDeclContextAttrs,
+    IdentifierContext, ListPatternContext, ListPatternContextAttrs, LoxParserContextType,
+    MatchArmContextAttrs, MatchStmtContext, MatchStmtContextAttrs, ParameterContextAttrs,
+    PatternAtomContextAttrs, ProgramContext, ProgramContextAttrs, RestParameterContextAttrs,
+    ReturnStmtContext, VarDeclContext, VarDeclContextAttrs, VariableContextAttrs,
+    VariantPatternContextAttrs, WhileStmtContext, YieldStmtContext, NUMBER,
+};
+use crate::antlr::loxvisitor::LoxVisitorCompat;
+use crate::generator;
+use crate::natives;
+use crate::number;
+use crate::syslib;
+use antlr_rust::parser_rule_context::ParserRuleContext;
+use antlr_rust::token::Token;
+use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat, TerminalNode, Tree};
+
+/*
+
+    ResolveVisit makes a pass over the tree after parsing but before anything runs.
+    It checks that every variable reference has a declaration in scope and reports
+    the scoping mistakes that would otherwise only show up at runtime, after the
+    script has already done part of its work.
+    It also binds every variable reference to the scope it is declared in,
+    InterpVisit reads the variable from that scope so that a function sees the
+    variables around its declaration, not the ones around its call.
+    Scopes are opened exactly where InterpVisit opens them, in particular a
+    function body shares its scope with the parameters, so that the index of a
+    scope here is one more than its index in InterpVisit::state, where the
+    builtins and the globals share the first scope.
+
+*/
+pub struct ResolveVisit {
+    pub errors: Vec<String>,
+    // the index in InterpVisit::state of the scope each reference resolved to,
+    // by the index of the reference's token
+    pub locals: HashMap<isize, usize>,
+    // name -> has its initializer finished
+    scopes: Vec<HashMap<String, bool>>,
+    // top level names, function bodies may use them before they are declared
+    hoisted: HashSet<String>,
+    function_depth: u32,
+    // the function being resolved has a yield in it
+    in_generator: bool,
+    loop_depth: u32,
+    val: (),
+}
+
+impl ResolveVisit {
+    pub fn new() -> Self {
+        let mut builtins = HashMap::new();
+        for native in natives::globals() {
+            builtins.insert(native.name.to_string(), true);
+        }
+        for (name, _) in natives::constants() {
+            builtins.insert(name.to_string(), true);
+        }
+        builtins.insert(syslib::ARGS.to_string(), true);
+        Self {
+            errors: Vec::new(),
+            locals: HashMap::new(),
+            // builtins get a scope of their own so that a script may declare
+            // a global with the same name
+            scopes: vec![builtins, HashMap::new()],
+            hoisted: HashSet::new(),
+            function_depth: 0,
+            in_generator: false,
+            loop_depth: 0,
+            val: (),
+        }
+    }
+    fn error(&mut self, line: isize, column: isize, msg: String) {
+        self.errors
+            .push(format!("line {}:{} {}", line, column, msg));
+    }
+    fn declare(&mut self, name: &str, line: isize, column: isize) {
+        let top = self.scopes.len() - 1;
+        if self.scopes[top].contains_key(name) {
+            self.error(
+                line,
+                column,
+                format!("{} is already declared in this scope", name),
+            );
+        }
+        self.scopes[top].insert(name.to_string(), false);
+    }
+    fn define(&mut self, name: &str) {
+        let top = self.scopes.len() - 1;
+        self.scopes[top].insert(name.to_string(), true);
+    }
+    fn resolve(&mut self, name: &str, token: isize, line: isize, column: isize) {
+        let found = self
+            .scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, scope)| scope.get(name).map(|defined| (i, *defined)));
+        if let Some((i, defined)) = found {
+            self.locals.insert(token, i.saturating_sub(1));
+            if !defined {
+                self.error(
+                    line,
+                    column,
+                    format!("cannot read {} in its own initializer", name),
+                );
+            }
+            return;
+        }
+        if self.function_depth > 0 && self.hoisted.contains(name) {
+            // a global declared after the function
+            self.locals.insert(token, 0);
+            return;
+        }
+        self.error(line, column, format!("{} is not declared", name));
+    }
+}
+
+// the names a var declaration introduces, more than one if it destructures a list
+fn declared_names(ctx: &VarDeclContext<'_>) -> Vec<String> {
+    match ctx.target.as_ref() {
+        Some(target) => pattern_names(target),
+        None => vec![ctx.IDENTIFIER().unwrap().get_text()],
+    }
+}
+
+fn pattern_names(pattern: &ListPatternContext<'_>) -> Vec<String> {
+    let mut names: Vec<String> = pattern
+        .IDENTIFIER_all()
+        .iter()
+        .map(|id| id.get_text())
+        .collect();
+    if let Some(rest) = pattern.restParameter() {
+        names.push(rest.IDENTIFIER().unwrap().get_text());
+    }
+    names
+}
+
+impl<'input> ParseTreeVisitorCompat<'input> for ResolveVisit {
+    type Node = LoxParserContextType;
+    type Return = ();
+    fn temp_result(&mut self) -> &mut Self::Return {
+        &mut self.val
+    }
+    fn visit_terminal(&mut self, node: &TerminalNode<'input, Self::Node>) -> Self::Return {
+        // malformed number literals are reported here rather than when they run
+        if node.symbol.get_token_type() == NUMBER {
+            if let Err(msg) = number::parse_number(&node.get_text()) {
+                self.error(node.symbol.get_line(), node.symbol.get_column(), msg);
+            }
+        }
+    }
+}
+
+impl<'a> LoxVisitorCompat<'a> for ResolveVisit {
+    fn visit_program(&mut self, ctx: &ProgramContext<'a>) -> Self::Return {
+        for decl in ctx.declaration_all() {
+            let names = match decl.as_ref() {
+                DeclarationContextAll::VariableContext(c) => declared_names(&c.varDecl().unwrap()),
+                DeclarationContextAll::FunctionContext(c) => c
+                    .functionDecl()
+                    .unwrap()
+                    .IDENTIFIER()
+                    .into_iter()
+                    .map(|id| id.get_text())
+                    .collect(),
+                DeclarationContextAll::EnumerationContext(c) => c
+                    .enumDecl()
+                    .unwrap()
+                    .enumVariant_all()
+                    .iter()
+                    .map(|variant| variant.IDENTIFIER(0).unwrap().get_text())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            self.hoisted.extend(names);
+        }
+        self.visit_children(ctx)
+    }
+    fn visit_block(&mut self, ctx: &BlockContext<'a>) -> Self::Return {
+        self.scopes.push(HashMap::new());
+        self.visit_children(ctx);
+        self.scopes.pop();
+    }
+    fn visit_varDecl(&mut self, ctx: &VarDeclContext<'a>) -> Self::Return {
+        let names = declared_names(ctx);
+        for name in names.iter() {
+            self.declare(name, ctx.start().get_line(), ctx.start().get_column());
+        }
+        if let Some(expr) = ctx.expr.as_ref() {
+            self.visit(&*expr.as_ref());
+        }
+        for name in names.iter() {
+            self.define(name);
+        }
+    }
+    fn visit_functionDecl(&mut self, ctx: &FunctionDeclContext<'a>) -> Self::Return {
+        let id = ctx.IDENTIFIER().unwrap().get_text();
+        // defined straight away so that the function can call itself
+        self.declare(&id, ctx.start().get_line(), ctx.start().get_column());
+        self.define(&id);
+
+        let enclosing_loops = self.loop_depth;
+        self.loop_depth = 0;
+        let body = ctx.body.as_ref().unwrap();
+        let enclosing_generator =
+            std::mem::replace(&mut self.in_generator, generator::contains_yield(&**body));
+        self.function_depth += 1;
+        self.scopes.push(HashMap::new());
+        if let Some(params) = ctx.parameters() {
+            for param in params.params.iter() {
+                // a default can only see the parameters before it
+                if let Some(value) = param.value.as_ref() {
+                    self.visit(&*value.as_ref());
+                }
+                let name = param.IDENTIFIER().unwrap().get_text();
+                self.declare(&name, param.start().get_line(), param.start().get_column());
+                self.define(&name);
+            }
+            if let Some(rest) = params.rest.as_ref() {
+                let name = rest.IDENTIFIER().unwrap().get_text();
+                self.declare(&name, rest.start().get_line(), rest.start().get_column());
+                self.define(&name);
+            }
+        }
+        for node in body.get_children() {
+            self.visit(node.as_ref());
+        }
+        self.scopes.pop();
+        self.function_depth -= 1;
+        self.in_generator = enclosing_generator;
+        self.loop_depth = enclosing_loops;
+    }
+    fn visit_enumDecl(&mut self, ctx: &EnumDeclContext<'a>) -> Self::Return {
+        for variant in ctx.enumVariant_all() {
+            let name = variant.IDENTIFIER(0).unwrap().get_text();
+            self.declare(
+                &name,
+                variant.start().get_line(),
+                variant.start().get_column(),
+            );
+            self.define(&name);
+        }
+    }
+    fn visit_identifier(&mut self, ctx: &IdentifierContext<'a>) -> Self::Return {
+        let id = ctx.get_text();
+        let token = ctx.start();
+        self.resolve(
+            &id,
+            token.get_token_index(),
+            token.get_line(),
+            token.get_column(),
+        );
+    }
+    fn visit_assignment_alt(&mut self, ctx: &Assignment_altContext<'a>) -> Self::Return {
+        self.visit(&*ctx.iter.as_ref().unwrap().as_ref());
+        let id = ctx.IDENTIFIER().unwrap();
+        self.resolve(
+            &id.get_text(),
+            id.symbol.get_token_index(),
+            id.symbol.get_line(),
+            id.symbol.get_column(),
+        );
+    }
+    fn visit_destructure_alt(&mut self, ctx: &Destructure_altContext<'a>) -> Self::Return {
+        self.visit(&*ctx.value.as_ref().unwrap().as_ref());
+        let target = ctx.target.as_ref().unwrap();
+        let ids = target
+            .IDENTIFIER_all()
+            .into_iter()
+            .chain(target.restParameter().and_then(|rest| rest.IDENTIFIER()));
+        for id in ids {
+            self.resolve(
+                &id.get_text(),
+                id.symbol.get_token_index(),
+                id.symbol.get_line(),
+                id.symbol.get_column(),
+            );
+        }
+    }
+    fn visit_whileStmt(&mut self, ctx: &WhileStmtContext<'a>) -> Self::Return {
+        self.loop_depth += 1;
+        self.visit_children(ctx);
+        self.loop_depth -= 1;
+    }
+    fn visit_forStmt(&mut self, ctx: &ForStmtContext<'a>) -> Self::Return {
+        // the loop variable lives in its own scope around the loop
+        self.scopes.push(HashMap::new());
+        self.loop_depth += 1;
+        self.visit_children(ctx);
+        self.loop_depth -= 1;
+        self.scopes.pop();
+    }
+    fn visit_forInStmt(&mut self, ctx: &ForInStmtContext<'a>) -> Self::Return {
+        self.visit(&*ctx.iterable.as_ref().unwrap().as_ref());
+        self.scopes.push(HashMap::new());
+        self.define(&ctx.IDENTIFIER().unwrap().get_text());
+        self.loop_depth += 1;
+        self.visit(&*ctx.body.as_ref().unwrap().as_ref());
+        self.loop_depth -= 1;
+        self.scopes.pop();
+    }
+    fn visit_matchStmt(&mut self, ctx: &MatchStmtContext<'a>) -> Self::Return {
+        self.visit(&*ctx.subject.as_ref().unwrap().as_ref());
+        for arm in ctx.matchArm_all() {
+            self.scopes.push(HashMap::new());
+            for atom in arm.pattern().unwrap().alternatives.iter() {
+                if let Some(id) = atom.IDENTIFIER() {
+                    self.define(&id.get_text());
+                }
+                for bound in [atom.low.as_ref(), atom.high.as_ref()]
+                    .into_iter()
+                    .flatten()
+                {
+                    self.visit(&*bound.as_ref());
+                }
+                if let Some(variant) = atom.variantPattern() {
+                    let ids = variant.IDENTIFIER_all();
+                    self.resolve(
+                        &ids[0].get_text(),
+                        ids[0].symbol.get_token_index(),
+                        variant.start().get_line(),
+                        variant.start().get_column(),
+                    );
+                    for id in &ids[1..] {
+                        self.define(&id.get_text());
+                    }
+                }
+                if let Some(list) = atom.listPattern() {
+                    for name in pattern_names(&list) {
+                        self.define(&name);
+                    }
+                }
+            }
+            if let Some(guard) = arm.guard.as_ref() {
+                self.visit(&*guard.as_ref());
+            }
+            self.visit(&*arm.body.as_ref().unwrap().as_ref());
+            self.scopes.pop();
+        }
+    }
+    fn visit_breakStmt(&mut self, ctx: &BreakStmtContext<'a>) -> Self::Return {
+        if self.loop_depth == 0 {
+            self.error(
+                ctx.start().get_line(),
+                ctx.start().get_column(),
+                "break outside of loop".to_string(),
+            );
+        }
+    }
+    fn visit_continueStmt(&mut self, ctx: &ContinueStmtContext<'a>) -> Self::Return {
+        if self.loop_depth == 0 {
+            self.error(
+                ctx.start().get_line(),
+                ctx.start().get_column(),
+                "continue outside of loop".to_string(),
+            );
+        }
+    }
+    fn visit_returnStmt(&mut self, ctx: &ReturnStmtContext<'a>) -> Self::Return {
+        if self.function_depth == 0 {
+            self.error(
+                ctx.start().get_line(),
+                ctx.start().get_column(),
+                "return outside of a function".to_string(),
+            );
+        } else if self.in_generator && ctx.value.is_some() {
+            // the values of a generator are the ones it yields
+            self.error(
+                ctx.start().get_line(),
+                ctx.start().get_column(),
+                "a generator cannot return a value".to_string(),
+            );
+        }
+        self.visit_children(ctx)
+    }
+    fn visit_yieldStmt(&mut self, ctx: &YieldStmtContext<'a>) -> Self::Return {
+        if self.function_depth == 0 {
+            self.error(
+                ctx.start().get_line(),
+                ctx.start().get_column(),
+                "yield outside of a function".to_string(),
+            );
+        }
+        self.visit_children(ctx)
+    }
+}
@@ -0,0 +1,239 @@
+use std::rc::Rc;
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+use crate::natives::{
+    bool_value, count_arg, native, number_arg, string_arg, type_name, NativeFunction,
+};
+use crate::number;
+
+/*
+
+    The string library, bound as globals.
+    Every index and length is counted in characters (unicode scalar values),
+    not bytes, so "né" has length 2 and substring("né", 1) is "é".
+
+*/
+// the longest string, in bytes, that repeat will build
+const MAX_STRING: usize = 1 << 30;
+
+pub const STRING: &[NativeFunction] = &[
+    native("len", 1, 1, len),
+    native("substring", 2, 3, substring),
+    native("split", 2, 2, split),
+    native("join", 2, 2, join),
+    native("trim", 1, 1, trim),
+    native("upper", 1, 1, upper),
+    native("lower", 1, 1, lower),
+    native("find", 2, 2, find),
+    native("contains", 2, 2, contains),
+    native("replace", 3, 3, replace),
+    native("starts_with", 2, 2, starts_with),
+    native("ends_with", 2, 2, ends_with),
+    native("repeat", 2, 2, repeat),
+    native("ord", 1, 1, ord),
+    native("chr", 1, 1, chr),
+    native("to_number", 1, 1, to_number),
+];
+
+fn string(s: impl Into<String>) -> TermValue {
+    TermValue::StringValue(s.into())
+}
+
+// len(x), the number of characters in a string or elements in a list
+fn len(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match &args[0] {
+        TermValue::StringValue(s) => TermValue::Number(s.chars().count() as f64),
+        TermValue::List(items) => TermValue::Number(items.len() as f64),
+        arg => TermValue::Error(format!(
+            "len expects a string or a list, got {}",
+            type_name(arg)
+        )),
+    }
+}
+
+// substring(s, start, end), the characters from start up to but not including end,
+// end defaults to the end of the string
+fn substring(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match char_range(args) {
+        Ok(s) => string(s),
+        Err(err) => err,
+    }
+}
+
+fn char_range(args: &[TermValue]) -> Result<String, TermValue> {
+    let chars: Vec<char> = string_arg("substring", args, 0)?.chars().collect();
+    let start = count_arg("substring", args, 1)?;
+    let end = match args.get(2) {
+        Some(_) => count_arg("substring", args, 2)?,
+        None => chars.len(),
+    };
+    if start > end || end > chars.len() {
+        return Err(TermValue::Error(format!(
+            "substring range {}..{} is out of bounds for a string of length {}",
+            start,
+            end,
+            chars.len()
+        )));
+    }
+    Ok(chars[start..end].iter().collect())
+}
+
+// split(s, sep) gives a list of strings, an empty sep splits into characters
+fn split(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let (s, sep) = match (string_arg("split", args, 0), string_arg("split", args, 1)) {
+        (Ok(s), Ok(sep)) => (s, sep),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    let parts: Vec<TermValue> = if sep.is_empty() {
+        s.chars().map(string).collect()
+    } else {
+        s.split(sep).map(string).collect()
+    };
+    TermValue::List(Rc::new(parts))
+}
+
+// join(list, sep), elements that are not strings are joined as print shows them
+fn join(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let TermValue::List(items) = &args[0] else {
+        return TermValue::Error(format!(
+            "join expects a list as argument 1, got {}",
+            type_name(&args[0])
+        ));
+    };
+    let sep = match string_arg("join", args, 1) {
+        Ok(sep) => sep,
+        Err(err) => return err,
+    };
+    let parts: Vec<String> = items.iter().map(|item| interp.display(item)).collect();
+    string(parts.join(sep))
+}
+
+// applies f to the single string argument
+fn map_string(name: &str, args: &[TermValue], f: fn(&str) -> String) -> TermValue {
+    match string_arg(name, args, 0) {
+        Ok(s) => string(f(s)),
+        Err(err) => err,
+    }
+}
+
+fn trim(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    map_string("trim", args, |s| s.trim().to_string())
+}
+
+fn upper(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    map_string("upper", args, str::to_uppercase)
+}
+
+fn lower(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    map_string("lower", args, str::to_lowercase)
+}
+
+// applies f to the two string arguments
+fn two_strings(name: &str, args: &[TermValue], f: fn(&str, &str) -> TermValue) -> TermValue {
+    match (string_arg(name, args, 0), string_arg(name, args, 1)) {
+        (Ok(a), Ok(b)) => f(a, b),
+        (Err(err), _) | (_, Err(err)) => err,
+    }
+}
+
+// find(s, sub), the index of the first sub in s or -1
+fn find(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    two_strings("find", args, |s, sub| match s.find(sub) {
+        Some(byte) => TermValue::Number(s[..byte].chars().count() as f64),
+        None => TermValue::Number(-1.0),
+    })
+}
+
+fn contains(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    two_strings("contains", args, |s, sub| bool_value(s.contains(sub)))
+}
+
+fn starts_with(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    two_strings("starts_with", args, |s, prefix| {
+        bool_value(s.starts_with(prefix))
+    })
+}
+
+fn ends_with(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    two_strings("ends_with", args, |s, suffix| {
+        bool_value(s.ends_with(suffix))
+    })
+}
+
+// replace(s, from, to) replaces every from
+fn replace(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let strings: Result<Vec<&str>, TermValue> =
+        (0..3).map(|i| string_arg("replace", args, i)).collect();
+    match strings.as_deref() {
+        Ok([_, "", _]) => TermValue::Error("replace cannot replace an empty string".to_string()),
+        Ok([s, from, to]) => string(s.replace(from, to)),
+        Ok(_) => unreachable!(),
+        Err(err) => err.clone(),
+    }
+}
+
+// repeat(s, n), s n times over
+fn repeat(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    match (string_arg("repeat", args, 0), count_arg("repeat", args, 1)) {
+        (Ok(s), Ok(n)) => match s.len().checked_mul(n) {
+            Some(size) if size <= MAX_STRING => string(s.repeat(n)),
+            _ => TermValue::Error(format!(
+                "repeat would make a string of more than {} bytes",
+                MAX_STRING
+            )),
+        },
+        (Err(err), _) | (_, Err(err)) => err,
+    }
+}
+
+// ord(c), the code point of a one character string
+fn ord(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let s = match string_arg("ord", args, 0) {
+        Ok(s) => s,
+        Err(err) => return err,
+    };
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => TermValue::Number(c as u32 as f64),
+        _ => TermValue::Error(format!(
+            "ord expects a single character, got a string of length {}",
+            s.chars().count()
+        )),
+    }
+}
+
+// chr(n), the one character string for code point n
+fn chr(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let code = match number_arg("chr", args, 0) {
+        Ok(code) => code,
+        Err(err) => return err,
+    };
+    let c = (code >= 0.0 && code.fract() == 0.0 && code <= u32::MAX as f64)
+        .then(|| char::from_u32(code as u32))
+        .flatten();
+    match c {
+        Some(c) => string(c),
+        None => TermValue::Error(format!("chr: {} is not a valid code point", code)),
+    }
+}
+
+// to_number(s) reads a number written as in lox source, with an optional sign,
+// it gives nil rather than an error when s is not a number so scripts can check
+fn to_number(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let s = match string_arg("to_number", args, 0) {
+        Ok(s) => s.trim(),
+        Err(err) => return err,
+    };
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return TermValue::Nil;
+    }
+    match number::parse_number(digits) {
+        Ok(x) if negative => TermValue::Number(-x),
+        Ok(x) => TermValue::Number(x),
+        Err(_) => TermValue::Nil,
+    }
+}
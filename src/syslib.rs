@@ -0,0 +1,70 @@
+use std::env;
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+use crate::natives::{native, number_arg, string_arg, NativeFunction};
+
+/*
+
+    The process library, bound as globals, next to the args list that holds
+    the command line arguments given after the script.
+    exit() stops the script at once, so any statements after it do not run.
+    It records the status on the interpreter and unwinds like a runtime error,
+    main then ends the process with that status instead of reporting an error.
+
+*/
+pub const SYS: &[NativeFunction] = &[
+    native("getenv", 1, 1, getenv),
+    native("setenv", 2, 2, setenv),
+    native("exit", 0, 1, exit),
+];
+
+// the global that holds the script's command line arguments as a list of strings
+pub const ARGS: &str = "args";
+
+// getenv(name), the value of an environment variable, nil when it is not set
+fn getenv(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let name = match string_arg("getenv", args, 0) {
+        Ok(name) => name,
+        Err(err) => return err,
+    };
+    match env::var_os(name) {
+        Some(value) => TermValue::StringValue(value.to_string_lossy().into_owned()),
+        None => TermValue::Nil,
+    }
+}
+
+// setenv(name, value), for this process and the ones it starts
+fn setenv(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let (name, value) = match (string_arg("setenv", args, 0), string_arg("setenv", args, 1)) {
+        (Ok(name), Ok(value)) => (name, value),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    // set_var panics on these rather than returning an error
+    if name.is_empty() || name.contains(['=', '\0']) {
+        return TermValue::Error(format!("setenv: {:?} is not a valid variable name", name));
+    }
+    if value.contains('\0') {
+        return TermValue::Error("setenv: a value cannot contain a NUL character".to_string());
+    }
+    env::set_var(name, value);
+    TermValue::Nil
+}
+
+// exit(code) ends the interpreter with that exit status, 0 when it is left out
+fn exit(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let code = match args.first() {
+        Some(_) => match number_arg("exit", args, 0) {
+            Ok(code) if code.fract() == 0.0 && (0.0..=255.0).contains(&code) => code as i32,
+            Ok(code) => {
+                return TermValue::Error(format!(
+                    "exit expects a whole number from 0 to 255, got {}",
+                    code
+                ))
+            }
+            Err(err) => return err,
+        },
+        None => 0,
+    };
+    interp.exit_code = Some(code);
+    TermValue::Error(format!("exit({})", code))
+}
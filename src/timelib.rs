@@ -0,0 +1,312 @@
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::interpvisitor::{InterpVisit, TermValue};
+use crate::natives::{native, number_arg, string_arg, NativeFunction};
+
+/*
+
+    The clock and date library, bound as globals.
+    Times are numbers of seconds since the unix epoch and durations are numbers
+    of seconds, so time arithmetic is plain arithmetic: time() + 90 is a minute
+    and a half from now and t2 - t1 is the duration between two times.
+    Dates are formatted and parsed in UTC with the specifiers %Y %m %d %H %M %S,
+    %f for milliseconds and %% for a percent sign.
+    The interpreter reads the time through its Clock, which a test run replaces
+    with a FakeClock (the --fake-clock option) to get the same output every run.
+
+*/
+pub const TIME: &[NativeFunction] = &[
+    native("clock", 0, 0, clock),
+    native("time", 0, 0, time),
+    native("sleep", 1, 1, sleep),
+    native("format_time", 1, 2, format_time),
+    native("parse_time", 1, 2, parse_time),
+    native("format_duration", 1, 1, format_duration),
+];
+
+const DEFAULT_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+// times this far from the epoch (about three million years) are rejected
+// rather than overflowing the date arithmetic
+const MAX_SECONDS: f64 = 1e14;
+
+pub trait Clock {
+    // seconds since the unix epoch
+    fn now(&self) -> f64;
+    // seconds since the interpreter started, never going backwards
+    fn elapsed(&self) -> f64;
+    fn sleep(&mut self, duration: Duration);
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs_f64(),
+            Err(err) => -err.duration().as_secs_f64(),
+        }
+    }
+    fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+// a clock that stands still except when a script sleeps, which returns at once
+pub struct FakeClock {
+    start: f64,
+    elapsed: f64,
+}
+
+impl FakeClock {
+    pub fn new(start: f64) -> Self {
+        Self {
+            start,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> f64 {
+        self.start + self.elapsed
+    }
+    fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+    fn sleep(&mut self, duration: Duration) {
+        self.elapsed += duration.as_secs_f64();
+    }
+}
+
+// clock(), seconds since the interpreter started, for timing scripts
+fn clock(interp: &mut InterpVisit, _args: &[TermValue]) -> TermValue {
+    TermValue::Number(interp.clock.elapsed())
+}
+
+// time(), the current time
+fn time(interp: &mut InterpVisit, _args: &[TermValue]) -> TermValue {
+    TermValue::Number(interp.clock.now())
+}
+
+// sleep(seconds)
+fn sleep(interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let seconds = match number_arg("sleep", args, 0) {
+        Ok(seconds) => seconds,
+        Err(err) => return err,
+    };
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) => {
+            interp.clock.sleep(duration);
+            TermValue::Nil
+        }
+        Err(_) => TermValue::Error(format!(
+            "sleep expects a number of seconds >= 0, got {}",
+            seconds
+        )),
+    }
+}
+
+// argument i, a time or a duration within MAX_SECONDS of zero
+fn seconds_arg(name: &str, args: &[TermValue], i: usize) -> Result<f64, TermValue> {
+    let seconds = number_arg(name, args, i)?;
+    if !(-MAX_SECONDS..=MAX_SECONDS).contains(&seconds) {
+        return Err(TermValue::Error(format!(
+            "{} expects a number of seconds, got {}",
+            name, seconds
+        )));
+    }
+    Ok(seconds)
+}
+
+// the optional format argument i
+fn format_arg<'v>(name: &str, args: &'v [TermValue], i: usize) -> Result<&'v str, TermValue> {
+    match args.get(i) {
+        Some(_) => string_arg(name, args, i),
+        None => Ok(DEFAULT_FORMAT),
+    }
+}
+
+// days since 1970-01-01 of a date in the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// the (year, month, day) of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)
+}
+
+// format_time(t, format), t as a date in UTC
+fn format_time(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let (t, format) = match (
+        seconds_arg("format_time", args, 0),
+        format_arg("format_time", args, 1),
+    ) {
+        (Ok(t), Ok(format)) => (t, format),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    match format_utc(t, format) {
+        Ok(text) => TermValue::StringValue(text),
+        Err(msg) => TermValue::Error(format!("format_time: {}", msg)),
+    }
+}
+
+fn format_utc(t: f64, format: &str) -> Result<String, String> {
+    let millis = (t * 1000.0).round() as i64;
+    let seconds = millis.div_euclid(1000);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let of_day = seconds.rem_euclid(86400);
+    let mut text = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => text.push_str(&format!("{:04}", year)),
+            Some('m') => text.push_str(&format!("{:02}", month)),
+            Some('d') => text.push_str(&format!("{:02}", day)),
+            Some('H') => text.push_str(&format!("{:02}", of_day / 3600)),
+            Some('M') => text.push_str(&format!("{:02}", of_day / 60 % 60)),
+            Some('S') => text.push_str(&format!("{:02}", of_day % 60)),
+            Some('f') => text.push_str(&format!("{:03}", millis.rem_euclid(1000))),
+            Some('%') => text.push('%'),
+            Some(other) => return Err(format!("unknown format %{}", other)),
+            None => return Err("format ends with a lone %".to_string()),
+        }
+    }
+    Ok(text)
+}
+
+// parse_time(s, format), the time of a date in UTC written in format
+fn parse_time(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let (text, format) = match (
+        string_arg("parse_time", args, 0),
+        format_arg("parse_time", args, 1),
+    ) {
+        (Ok(text), Ok(format)) => (text, format),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    match parse_utc(text, format) {
+        Ok(t) => TermValue::Number(t),
+        Err(msg) => TermValue::Error(format!("parse_time: {}", msg)),
+    }
+}
+
+fn parse_utc(text: &str, format: &str) -> Result<f64, String> {
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second, mut millis) = (0, 0, 0, 0);
+    // rest is the part of text not matched yet
+    let mismatch = |rest: &str| {
+        format!(
+            "{:?} does not match the format {:?} at character {}",
+            text,
+            format,
+            text[..text.len() - rest.len()].chars().count()
+        )
+    };
+    let mut rest = text;
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            rest = rest.strip_prefix(c).ok_or_else(|| mismatch(rest))?;
+            continue;
+        }
+        let (field, width) = match chars.next() {
+            Some('Y') => (&mut year, 4),
+            Some('m') => (&mut month, 2),
+            Some('d') => (&mut day, 2),
+            Some('H') => (&mut hour, 2),
+            Some('M') => (&mut minute, 2),
+            Some('S') => (&mut second, 2),
+            Some('f') => (&mut millis, 3),
+            Some('%') => {
+                rest = rest.strip_prefix('%').ok_or_else(|| mismatch(rest))?;
+                continue;
+            }
+            Some(other) => return Err(format!("unknown format %{}", other)),
+            None => return Err("format ends with a lone %".to_string()),
+        };
+        let digits = rest
+            .get(..width)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+            .ok_or_else(|| mismatch(rest))?;
+        *field = digits.parse::<i64>().unwrap();
+        rest = &rest[width..];
+    }
+    if !rest.is_empty() {
+        return Err(mismatch(rest));
+    }
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(format!("{:?} is not a valid date", text));
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return Err(format!("{:?} is not a valid time of day", text));
+    }
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    Ok(seconds as f64 + millis as f64 / 1000.0)
+}
+
+// format_duration(seconds), e.g. "1h 0m 30s" or "2.5s"
+fn format_duration(_interp: &mut InterpVisit, args: &[TermValue]) -> TermValue {
+    let seconds = match seconds_arg("format_duration", args, 0) {
+        Ok(seconds) => seconds,
+        Err(err) => return err,
+    };
+    let total = (seconds.abs() * 1000.0).round() as i64;
+    let mut millis = total;
+    let mut parts = Vec::new();
+    for (unit, size) in [("d", 86_400_000), ("h", 3_600_000), ("m", 60_000)] {
+        if millis >= size || !parts.is_empty() {
+            parts.push(format!("{}{}", millis / size, unit));
+            millis %= size;
+        }
+    }
+    parts.push(format!("{}s", millis as f64 / 1000.0));
+    let sign = if seconds < 0.0 && total > 0 { "-" } else { "" };
+    TermValue::StringValue(format!("{}{}", sign, parts.join(" ")))
+}
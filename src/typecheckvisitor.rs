@@ -0,0 +1,717 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::antlr::loxparser::{
+    ArgumentContextAttrs, ArgumentsContextAttrs, Assignment_altContext, Assignment_altContextAttrs,
+    BlockContext, Bool_falseContext, Bool_trueContext, CallfunContext, CallfunContextAttrs,
+    ComparisonContext, Destructure_altContext, EnumDeclContext, EnumDeclContextAttrs,
+    EnumVariantContextAttrs, EqualityContext, FactorContext, FactorContextAttrs, ForInStmtContext,
+    ForInStmtContextAttrs, ForStmtContext, FunctionDeclContext, FunctionDeclContextAttrs,
+    GroupContext, GroupContextAttrs, IdentifierContext, IfStmtContext, ListContext,
+    ListContextAttrs, ListPatternContextAll, ListPatternContextAttrs, LiteralContext,
+    LiteralContextAttrs, Logic_andContext, Logic_orContext, LoxParserContext, LoxParserContextType,
+    MatchArmContextAttrs, MatchStmtContext, MatchStmtContextAttrs, NilContext, NumberContext,
+    ParameterContextAttrs, PatternAtomContextAttrs, RangeExprContext, RestParameterContextAttrs,
+    ReturnStmtContext, StrvalContext, TermContext, TermContextAttrs, TypeNameContextAll,
+    Unary_altContext, Unary_altContextAttrs, VarDeclContext, VarDeclContextAttrs,
+    VariantPatternContextAttrs, WhileStmtContext,
+};
+use crate::antlr::loxvisitor::LoxVisitorCompat;
+use crate::generator;
+use crate::natives;
+use crate::syslib;
+use antlr_rust::parser_rule_context::ParserRuleContext;
+use antlr_rust::token::Token;
+use antlr_rust::tree::{ParseTree, ParseTreeVisitorCompat, Tree};
+
+/*
+
+    TypeCheckVisit checks the optional type annotations without running anything.
+    Unannotated variables take the type of their initializer, anything that cannot
+    be worked out statically is Any and is compatible with every type.
+    Only an annotated variable constrains what can be assigned to it later, the
+    type of an unannotated one is just what is known about its current value.
+    All errors are collected so that they can be reported at once.
+    The annotations are only used here, at runtime lox stays dynamically typed.
+
+*/
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum LoxType {
+    #[default]
+    Any,
+    Number,
+    Str,
+    Bool,
+    Nil,
+    List,
+    Range,
+    Function,
+    Generator,
+    Enum,
+}
+
+impl LoxType {
+    fn from_name(name: &str) -> Option<LoxType> {
+        match name {
+            "any" => Some(LoxType::Any),
+            "number" => Some(LoxType::Number),
+            "string" => Some(LoxType::Str),
+            "bool" => Some(LoxType::Bool),
+            "nil" => Some(LoxType::Nil),
+            "list" => Some(LoxType::List),
+            "range" => Some(LoxType::Range),
+            "fun" => Some(LoxType::Function),
+            "generator" => Some(LoxType::Generator),
+            "enum" => Some(LoxType::Enum),
+            _ => None,
+        }
+    }
+    fn accepts(&self, other: &LoxType) -> bool {
+        *self == LoxType::Any || *other == LoxType::Any || self == other
+    }
+}
+
+impl fmt::Display for LoxType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LoxType::Any => "any",
+            LoxType::Number => "number",
+            LoxType::Str => "string",
+            LoxType::Bool => "bool",
+            LoxType::Nil => "nil",
+            LoxType::List => "list",
+            LoxType::Range => "range",
+            LoxType::Function => "fun",
+            LoxType::Generator => "generator",
+            LoxType::Enum => "enum",
+        };
+        f.write_str(name)
+    }
+}
+
+struct Signature {
+    // name, type, has a default value
+    params: Vec<(String, LoxType, bool)>,
+    rest: bool,
+    ret: LoxType,
+}
+
+#[derive(Default)]
+struct Scope {
+    vars: HashMap<String, LoxType>,
+    signatures: HashMap<String, Rc<Signature>>,
+    // the variables declared with a type annotation
+    annotated: HashSet<String>,
+    // the scope of a function's parameters, the variables outside it may have
+    // been given other values by the time the function is called
+    function: bool,
+}
+
+pub struct TypeCheckVisit {
+    pub errors: Vec<String>,
+    scopes: Vec<Scope>,
+    // the name and annotated return type of each function being checked
+    returns: Vec<(String, Option<LoxType>)>,
+    // the element types of the list literals checked so far, by their first
+    // and last token, so that destructuring one can check each element
+    lists: HashMap<(isize, isize), Vec<LoxType>>,
+    val: LoxType,
+}
+
+impl TypeCheckVisit {
+    pub fn new() -> Self {
+        let mut globals = Scope::default();
+        for native in natives::globals() {
+            globals
+                .vars
+                .insert(native.name.to_string(), LoxType::Function);
+        }
+        for (name, _) in natives::constants() {
+            globals.vars.insert(name.to_string(), LoxType::Number);
+        }
+        globals.vars.insert(syslib::ARGS.to_string(), LoxType::List);
+        Self {
+            errors: Vec::new(),
+            scopes: vec![globals],
+            returns: Vec::new(),
+            lists: HashMap::new(),
+            val: LoxType::Any,
+        }
+    }
+    fn error(&mut self, ctx: &dyn LoxParserContext<'_>, msg: String) {
+        self.error_at(ctx.start().get_line(), ctx.start().get_column(), msg);
+    }
+    fn error_at(&mut self, line: isize, column: isize, msg: String) {
+        self.errors
+            .push(format!("line {}:{} {}", line, column, msg));
+    }
+    // the type of an expression that has to be of the expected type
+    fn expect<'a>(&mut self, ctx: &(dyn LoxParserContext<'a> + 'a), expected: LoxType, what: &str) {
+        let actual = self.visit(ctx);
+        if !expected.accepts(&actual) {
+            self.error(
+                ctx,
+                format!("{} must be {}, found {}", what, expected, actual),
+            );
+        }
+    }
+    fn annotation(&mut self, ctx: Option<&Rc<TypeNameContextAll<'_>>>) -> Option<LoxType> {
+        let ctx = ctx?;
+        let name = ctx.get_text();
+        match LoxType::from_name(&name) {
+            Some(ty) => Some(ty),
+            None => {
+                self.error(&**ctx, format!("unknown type {}", name));
+                Some(LoxType::Any)
+            }
+        }
+    }
+    fn declare(&mut self, name: String, ty: LoxType) {
+        let top = self.scopes.len() - 1;
+        self.scopes[top].annotated.remove(&name);
+        self.scopes[top].vars.insert(name, ty);
+    }
+    fn declare_annotated(&mut self, name: String, ty: LoxType) {
+        let top = self.scopes.len() - 1;
+        self.scopes[top].annotated.insert(name.clone());
+        self.scopes[top].vars.insert(name, ty);
+    }
+    fn lookup(&self, name: &str) -> LoxType {
+        let mut in_function = false;
+        for scope in self.scopes.iter().rev() {
+            if let Some(ty) = scope.vars.get(name) {
+                // only an annotation says what an outer variable holds when the
+                // function runs
+                if in_function && !scope.annotated.contains(name) {
+                    return LoxType::Any;
+                }
+                return ty.clone();
+            }
+            in_function |= scope.function;
+        }
+        LoxType::Any
+    }
+    // the annotated type of a variable, None when its declaration has no annotation
+    fn annotated(&self, name: &str) -> Option<LoxType> {
+        let scope = self
+            .scopes
+            .iter()
+            .rev()
+            .find(|s| s.vars.contains_key(name))?;
+        if !scope.annotated.contains(name) {
+            return None;
+        }
+        scope.vars.get(name).cloned()
+    }
+    // checks that name can be given a value of type val, an annotated variable
+    // has to accept it, an unannotated one may hold either value from here on
+    fn assign(&mut self, name: &str, val: &LoxType, line: isize, column: isize) {
+        match self.annotated(name) {
+            Some(declared) if !declared.accepts(val) => self.error_at(
+                line,
+                column,
+                format!("cannot assign {} to {} of type {}", val, name, declared),
+            ),
+            Some(_) => {}
+            None => {
+                let scope = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .find(|s| s.vars.contains_key(name));
+                if let Some(ty) = scope.and_then(|scope| scope.vars.get_mut(name)) {
+                    if ty != val {
+                        *ty = LoxType::Any;
+                    }
+                }
+            }
+        }
+    }
+    // the elements of a list are not tracked, only the rest is known to be a list
+    fn declare_pattern(&mut self, pattern: &ListPatternContextAll<'_>) {
+        for id in pattern.IDENTIFIER_all() {
+            self.declare(id.get_text(), LoxType::Any);
+        }
+        if let Some(rest) = pattern.restParameter() {
+            self.declare(rest.IDENTIFIER().unwrap().get_text(), LoxType::List);
+        }
+    }
+    fn signature(&self, name: &str) -> Option<Rc<Signature>> {
+        for scope in self.scopes.iter().rev() {
+            if scope.vars.contains_key(name) {
+                return scope.signatures.get(name).cloned();
+            }
+        }
+        None
+    }
+}
+
+impl ParseTreeVisitorCompat<'_> for TypeCheckVisit {
+    type Node = LoxParserContextType;
+    type Return = LoxType;
+    fn temp_result(&mut self) -> &mut Self::Return {
+        &mut self.val
+    }
+}
+
+impl<'a> LoxVisitorCompat<'a> for TypeCheckVisit {
+    fn visit_block(&mut self, ctx: &BlockContext<'a>) -> Self::Return {
+        self.scopes.push(Scope::default());
+        self.visit_children(ctx);
+        self.scopes.pop();
+        LoxType::Any
+    }
+    fn visit_varDecl(&mut self, ctx: &VarDeclContext<'a>) -> Self::Return {
+        if let Some(target) = ctx.target.as_ref() {
+            self.expect(
+                &**ctx.expr.as_ref().unwrap(),
+                LoxType::List,
+                "destructured value",
+            );
+            self.declare_pattern(target);
+            return LoxType::Any;
+        }
+        let id = ctx.IDENTIFIER().unwrap().get_text();
+        let declared = self.annotation(ctx.varType.as_ref());
+        let init = ctx.expr.as_ref().map(|expr| self.visit(&*expr.as_ref()));
+        match (declared, init) {
+            (Some(declared), Some(init)) => {
+                if !declared.accepts(&init) {
+                    self.error(
+                        ctx,
+                        format!(
+                            "cannot initialize {} of type {} with {}",
+                            id, declared, init
+                        ),
+                    );
+                }
+                self.declare_annotated(id, declared);
+            }
+            (Some(declared), None) => self.declare_annotated(id, declared),
+            // nil says nothing about what the variable will hold later
+            (None, Some(LoxType::Nil)) | (None, None) => self.declare(id, LoxType::Any),
+            (None, Some(init)) => self.declare(id, init),
+        }
+        LoxType::Any
+    }
+    fn visit_functionDecl(&mut self, ctx: &FunctionDeclContext<'a>) -> Self::Return {
+        let id = ctx.IDENTIFIER().unwrap().get_text();
+        let body = ctx.body.as_ref().unwrap();
+        let declared = self.annotation(ctx.returnType.as_ref());
+        let is_generator = generator::contains_yield(&**body);
+        // return statements are checked against the annotation, a generator's
+        // return has no value to check
+        let returns = if is_generator { None } else { declared.clone() };
+        // calling a function with a yield in it gives a generator, whatever it returns
+        let ret = match declared {
+            Some(declared) if is_generator => {
+                if !declared.accepts(&LoxType::Generator) {
+                    self.error(
+                        &**ctx.returnType.as_ref().unwrap(),
+                        format!("{} yields, so it returns generator, not {}", id, declared),
+                    );
+                }
+                LoxType::Generator
+            }
+            None if is_generator => LoxType::Generator,
+            declared => declared.unwrap_or(LoxType::Any),
+        };
+
+        // parameters are checked in a scope of their own, shared with the body
+        self.scopes.push(Scope {
+            function: true,
+            ..Scope::default()
+        });
+        let mut params = Vec::new();
+        let mut rest = false;
+        if let Some(parameters) = ctx.parameters() {
+            for param in parameters.params.iter() {
+                let name = param.IDENTIFIER().unwrap().get_text();
+                let declared = self.annotation(param.paramType.as_ref());
+                let default = param.value.as_ref().map(|v| self.visit(&*v.as_ref()));
+                let annotated = declared.is_some();
+                let ty = match (declared, &default) {
+                    (Some(declared), Some(default)) => {
+                        if !declared.accepts(default) {
+                            self.error(
+                                &**param,
+                                format!(
+                                    "default for {} must be {}, found {}",
+                                    name, declared, default
+                                ),
+                            );
+                        }
+                        declared
+                    }
+                    (Some(declared), None) => declared,
+                    (None, Some(LoxType::Nil)) | (None, None) => LoxType::Any,
+                    (None, Some(default)) => default.clone(),
+                };
+                if annotated {
+                    self.declare_annotated(name.clone(), ty.clone());
+                } else {
+                    self.declare(name.clone(), ty.clone());
+                }
+                params.push((name, ty, default.is_some()));
+            }
+            if let Some(r) = parameters.rest.as_ref() {
+                self.declare(r.IDENTIFIER().unwrap().get_text(), LoxType::List);
+                rest = true;
+            }
+        }
+        let signature = Rc::new(Signature { params, rest, ret });
+        // declared in the enclosing scope, but before the body so it can recurse
+        let enclosing = self.scopes.len() - 2;
+        self.scopes[enclosing]
+            .vars
+            .insert(id.clone(), LoxType::Function);
+        self.scopes[enclosing]
+            .signatures
+            .insert(id.clone(), signature);
+
+        self.returns.push((id, returns));
+        for node in body.get_children() {
+            self.visit(node.as_ref());
+        }
+        self.returns.pop();
+        self.scopes.pop();
+        LoxType::Any
+    }
+    fn visit_enumDecl(&mut self, ctx: &EnumDeclContext<'a>) -> Self::Return {
+        let top = self.scopes.len() - 1;
+        for variant in ctx.enumVariant_all() {
+            let ids = variant.IDENTIFIER_all();
+            let name = ids[0].get_text();
+            if ids.len() == 1 {
+                self.declare(name, LoxType::Enum);
+                continue;
+            }
+            // constructors are checked like functions taking one argument per field
+            let params = ids[1..]
+                .iter()
+                .map(|id| (id.get_text(), LoxType::Any, false))
+                .collect();
+            let signature = Rc::new(Signature {
+                params,
+                rest: false,
+                ret: LoxType::Enum,
+            });
+            self.declare(name.clone(), LoxType::Function);
+            self.scopes[top].signatures.insert(name, signature);
+        }
+        LoxType::Any
+    }
+    fn visit_ifStmt(&mut self, ctx: &IfStmtContext<'a>) -> Self::Return {
+        self.expect(
+            &**ctx.condition.as_ref().unwrap(),
+            LoxType::Bool,
+            "if condition",
+        );
+        self.visit(&*ctx.thenBranch.as_ref().unwrap().as_ref());
+        if let Some(else_branch) = ctx.elseBranch.as_ref() {
+            self.visit(&*else_branch.as_ref());
+        }
+        LoxType::Any
+    }
+    fn visit_returnStmt(&mut self, ctx: &ReturnStmtContext<'a>) -> Self::Return {
+        let actual = match ctx.value.as_ref() {
+            Some(value) => self.visit(&*value.as_ref()),
+            None => LoxType::Nil,
+        };
+        if let Some((name, Some(declared))) = self.returns.last() {
+            if !declared.accepts(&actual) {
+                let msg = format!("{} must return {}, found {}", name, declared, actual);
+                self.error(ctx, msg);
+            }
+        }
+        LoxType::Any
+    }
+    fn visit_whileStmt(&mut self, ctx: &WhileStmtContext<'a>) -> Self::Return {
+        self.expect(
+            &**ctx.condition.as_ref().unwrap(),
+            LoxType::Bool,
+            "while condition",
+        );
+        self.visit(&*ctx.body.as_ref().unwrap().as_ref());
+        LoxType::Any
+    }
+    fn visit_forStmt(&mut self, ctx: &ForStmtContext<'a>) -> Self::Return {
+        self.scopes.push(Scope::default());
+        if let Some(forvar) = ctx.forvar.as_ref() {
+            self.visit(&*forvar.as_ref());
+        } else if let Some(initializer) = ctx.initializer.as_ref() {
+            self.visit(&*initializer.as_ref());
+        }
+        if let Some(cond) = ctx.condition.as_ref() {
+            self.expect(&**cond, LoxType::Bool, "for condition");
+        }
+        if let Some(increment) = ctx.increment.as_ref() {
+            self.visit(&*increment.as_ref());
+        }
+        self.visit(&*ctx.body.as_ref().unwrap().as_ref());
+        self.scopes.pop();
+        LoxType::Any
+    }
+    fn visit_forInStmt(&mut self, ctx: &ForInStmtContext<'a>) -> Self::Return {
+        let iterable = ctx.iterable.as_ref().unwrap();
+        let item = match self.visit(&**iterable) {
+            LoxType::Range => LoxType::Number,
+            LoxType::Str => LoxType::Str,
+            LoxType::List | LoxType::Generator | LoxType::Any => LoxType::Any,
+            other => {
+                self.error(&**iterable, format!("{} is not iterable", other));
+                LoxType::Any
+            }
+        };
+        self.scopes.push(Scope::default());
+        self.declare(ctx.IDENTIFIER().unwrap().get_text(), item);
+        self.visit(&*ctx.body.as_ref().unwrap().as_ref());
+        self.scopes.pop();
+        LoxType::Any
+    }
+    fn visit_matchStmt(&mut self, ctx: &MatchStmtContext<'a>) -> Self::Return {
+        let subject = self.visit(&*ctx.subject.as_ref().unwrap().as_ref());
+        for arm in ctx.matchArm_all() {
+            self.scopes.push(Scope::default());
+            for atom in arm.pattern().unwrap().alternatives.iter() {
+                if let Some(id) = atom.IDENTIFIER() {
+                    self.declare(id.get_text(), subject.clone());
+                }
+                if let Some(variant) = atom.variantPattern() {
+                    for id in variant.IDENTIFIER_all().iter().skip(1) {
+                        self.declare(id.get_text(), LoxType::Any);
+                    }
+                }
+                if let Some(list) = atom.listPattern() {
+                    self.declare_pattern(&list);
+                }
+            }
+            if let Some(guard) = arm.guard.as_ref() {
+                self.expect(&**guard, LoxType::Bool, "match guard");
+            }
+            self.visit(&*arm.body.as_ref().unwrap().as_ref());
+            self.scopes.pop();
+        }
+        LoxType::Any
+    }
+    fn visit_destructure_alt(&mut self, ctx: &Destructure_altContext<'a>) -> Self::Return {
+        let value = ctx.value.as_ref().unwrap();
+        self.expect(&**value, LoxType::List, "destructured value");
+        // the element types are only known when a list literal is destructured
+        let span = (
+            value.start().get_token_index(),
+            value.stop().get_token_index(),
+        );
+        let elements = self.lists.get(&span).cloned().unwrap_or_default();
+        let target = ctx.target.as_ref().unwrap();
+        for (i, id) in target.IDENTIFIER_all().iter().enumerate() {
+            let val = elements.get(i).cloned().unwrap_or(LoxType::Any);
+            self.assign(
+                &id.get_text(),
+                &val,
+                id.symbol.get_line(),
+                id.symbol.get_column(),
+            );
+        }
+        if let Some(rest) = target.restParameter() {
+            let id = rest.IDENTIFIER().unwrap();
+            self.assign(
+                &id.get_text(),
+                &LoxType::List,
+                id.symbol.get_line(),
+                id.symbol.get_column(),
+            );
+        }
+        LoxType::List
+    }
+    fn visit_assignment_alt(&mut self, ctx: &Assignment_altContext<'a>) -> Self::Return {
+        let id = ctx.IDENTIFIER().unwrap().get_text();
+        let val = self.visit(&*ctx.iter.as_ref().unwrap().as_ref());
+        self.assign(&id, &val, ctx.start().get_line(), ctx.start().get_column());
+        val
+    }
+    // and / or evaluate to one of their operands
+    fn visit_logic_or(&mut self, ctx: &Logic_orContext<'a>) -> Self::Return {
+        let left = self.visit(&*ctx.left.as_ref().unwrap().as_ref());
+        let Some(right) = ctx.right.as_ref() else {
+            return left;
+        };
+        if self.visit(&*right.as_ref()) == left {
+            left
+        } else {
+            LoxType::Any
+        }
+    }
+    fn visit_logic_and(&mut self, ctx: &Logic_andContext<'a>) -> Self::Return {
+        let left = self.visit(&*ctx.left.as_ref().unwrap().as_ref());
+        let Some(right) = ctx.right.as_ref() else {
+            return left;
+        };
+        if self.visit(&*right.as_ref()) == left {
+            left
+        } else {
+            LoxType::Any
+        }
+    }
+    fn visit_equality(&mut self, ctx: &EqualityContext<'a>) -> Self::Return {
+        let left = self.visit(&*ctx.left.as_ref().unwrap().as_ref());
+        match ctx.right.as_ref() {
+            None => left,
+            Some(right) => {
+                self.visit(&*right.as_ref());
+                LoxType::Bool
+            }
+        }
+    }
+    fn visit_comparison(&mut self, ctx: &ComparisonContext<'a>) -> Self::Return {
+        let left = ctx.left.as_ref().unwrap();
+        match ctx.right.as_ref() {
+            None => self.visit(&**left),
+            Some(right) => {
+                self.expect(&**left, LoxType::Number, "comparison operand");
+                self.expect(&**right, LoxType::Number, "comparison operand");
+                LoxType::Bool
+            }
+        }
+    }
+    fn visit_rangeExpr(&mut self, ctx: &RangeExprContext<'a>) -> Self::Return {
+        let left = ctx.left.as_ref().unwrap();
+        match ctx.right.as_ref() {
+            None => self.visit(&**left),
+            Some(right) => {
+                self.expect(&**left, LoxType::Number, "range bound");
+                self.expect(&**right, LoxType::Number, "range bound");
+                LoxType::Range
+            }
+        }
+    }
+    fn visit_term(&mut self, ctx: &TermContext<'a>) -> Self::Return {
+        let operands = ctx.factor_all();
+        if operands.len() == 1 {
+            return self.visit(&*operands[0]);
+        }
+        for operand in operands.iter() {
+            self.expect(&**operand, LoxType::Number, "arithmetic operand");
+        }
+        LoxType::Number
+    }
+    fn visit_factor(&mut self, ctx: &FactorContext<'a>) -> Self::Return {
+        let operands = ctx.unary_all();
+        if operands.len() == 1 {
+            return self.visit(&*operands[0]);
+        }
+        for operand in operands.iter() {
+            self.expect(&**operand, LoxType::Number, "arithmetic operand");
+        }
+        LoxType::Number
+    }
+    fn visit_unary_alt(&mut self, ctx: &Unary_altContext<'a>) -> Self::Return {
+        let operand = ctx.unary().unwrap();
+        if ctx.MINUS().is_some() {
+            self.expect(&*operand, LoxType::Number, "negated value");
+            return LoxType::Number;
+        }
+        self.visit(&*operand);
+        LoxType::Bool
+    }
+    fn visit_callfun(&mut self, ctx: &CallfunContext<'a>) -> Self::Return {
+        let id = ctx.id.as_ref().unwrap().get_text();
+        let signature = self.signature(&id);
+        let mut positional = 0;
+        let mut supplied = Vec::new();
+        if let Some(arguments) = ctx.arguments() {
+            for arg in arguments.argument_all() {
+                let value = arg.value.as_ref().unwrap();
+                let ty = self.visit(&**value);
+                let Some(signature) = signature.as_ref() else {
+                    continue;
+                };
+                let param = match arg.IDENTIFIER() {
+                    Some(name) => {
+                        let name = name.get_text();
+                        let param = signature.params.iter().position(|p| p.0 == name);
+                        if param.is_none() {
+                            self.error(&*arg, format!("{} has no parameter named {}", id, name));
+                        }
+                        param
+                    }
+                    None => {
+                        positional += 1;
+                        if positional > signature.params.len() && !signature.rest {
+                            self.error(&*arg, format!("too many arguments for {}", id));
+                        }
+                        Some(positional - 1).filter(|i| *i < signature.params.len())
+                    }
+                };
+                if let Some(i) = param {
+                    let (name, expected, _) = &signature.params[i];
+                    if !expected.accepts(&ty) {
+                        self.error(
+                            &**value,
+                            format!(
+                                "argument {} of {} must be {}, found {}",
+                                name, id, expected, ty
+                            ),
+                        );
+                    }
+                    supplied.push(i);
+                }
+            }
+        }
+        match signature {
+            Some(signature) => {
+                for (i, (name, _, has_default)) in signature.params.iter().enumerate() {
+                    if !has_default && !supplied.contains(&i) {
+                        self.error(ctx, format!("missing argument {} for {}", name, id));
+                    }
+                }
+                signature.ret.clone()
+            }
+            None => LoxType::Any,
+        }
+    }
+    fn visit_group(&mut self, ctx: &GroupContext<'a>) -> Self::Return {
+        self.visit(&*ctx.expression().unwrap())
+    }
+    fn visit_identifier(&mut self, ctx: &IdentifierContext<'a>) -> Self::Return {
+        self.lookup(&ctx.get_text())
+    }
+    fn visit_literal(&mut self, ctx: &LiteralContext<'a>) -> Self::Return {
+        if ctx.NUMBER().is_some() {
+            return LoxType::Number;
+        }
+        if ctx.STRING().is_some() {
+            return LoxType::Str;
+        }
+        match ctx.get_text().as_str() {
+            "nil" => LoxType::Nil,
+            _ => LoxType::Bool,
+        }
+    }
+    fn visit_list(&mut self, ctx: &ListContext<'a>) -> Self::Return {
+        let elements = ctx
+            .expression_all()
+            .iter()
+            .map(|expr| self.visit(&**expr))
+            .collect();
+        let span = (ctx.start().get_token_index(), ctx.stop().get_token_index());
+        self.lists.insert(span, elements);
+        LoxType::List
+    }
+    fn visit_bool_true(&mut self, _ctx: &Bool_trueContext<'a>) -> Self::Return {
+        LoxType::Bool
+    }
+    fn visit_bool_false(&mut self, _ctx: &Bool_falseContext<'a>) -> Self::Return {
+        LoxType::Bool
+    }
+    fn visit_nil(&mut self, _ctx: &NilContext<'a>) -> Self::Return {
+        LoxType::Nil
+    }
+    fn visit_number(&mut self, _ctx: &NumberContext<'a>) -> Self::Return {
+        LoxType::Number
+    }
+    fn visit_strval(&mut self, _ctx: &StrvalContext<'a>) -> Self::Return {
+        LoxType::Str
+    }
+}
@@ -0,0 +1,124 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/*
+
+    Runs every script in tests/scripts and compares what it prints on stdout
+    with the .out file of the same name.
+    A script can start with "// args: ..." for the options to run it with,
+    "// script args: ..." for the arguments that follow the script,
+    "// status: N" for the exit status it has to end with, 0 when left out, and
+    "// error: ..." for text that has to appear on stderr, once per line.
+
+*/
+#[test]
+fn scripts() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("scripts");
+    let mut scripts: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty(), "no scripts in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for script in scripts.iter() {
+        if let Err(msg) = run(script) {
+            failures.push(format!("{}: {}", script.display(), msg));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+fn run(script: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(script).unwrap();
+    let expected = fs::read_to_string(script.with_extension("out"))
+        .map_err(|err| format!("cannot read the .out file: {}", err))?;
+    let mut args = Vec::new();
+    let mut script_args = Vec::new();
+    let mut status = 0;
+    let mut errors = Vec::new();
+    for line in source.lines() {
+        if let Some(rest) = line.strip_prefix("// args:") {
+            args.extend(rest.split_whitespace());
+        } else if let Some(rest) = line.strip_prefix("// script args:") {
+            script_args.extend(rest.split_whitespace());
+        } else if let Some(rest) = line.strip_prefix("// status:") {
+            status = rest.trim().parse().unwrap();
+        } else if let Some(rest) = line.strip_prefix("// error:") {
+            errors.push(rest.trim());
+        } else {
+            break;
+        }
+    }
+
+    // the interpreter writes its log to the working directory
+    let output = Command::new(env!("CARGO_BIN_EXE_mylox"))
+        .current_dir(env::temp_dir())
+        .args(args)
+        .arg(script)
+        .args(script_args)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.code() != Some(status) {
+        return Err(format!(
+            "exit status {:?}, expected {}\nstderr:\n{}",
+            output.status.code(),
+            status,
+            stderr
+        ));
+    }
+    if stdout != expected {
+        return Err(format!(
+            "stdout:\n{}\nexpected:\n{}\nstderr:\n{}",
+            stdout, expected, stderr
+        ));
+    }
+    for error in errors {
+        if !stderr.contains(error) {
+            return Err(format!(
+                "stderr does not contain {:?}\nstderr:\n{}",
+                error, stderr
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn missing_script() {
+    let output = Command::new(env!("CARGO_BIN_EXE_mylox"))
+        .current_dir(env::temp_dir())
+        .arg("no such script.lox")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(66));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("cannot read no such script.lox"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn syntax_error_reported_once() {
+    let script = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("scripts")
+        .join("syntax_error.lox");
+    let output = Command::new(env!("CARGO_BIN_EXE_mylox"))
+        .current_dir(env::temp_dir())
+        .arg(script)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+}